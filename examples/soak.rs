@@ -0,0 +1,58 @@
+use gpm_in_rust::{ControlChars, GpmVm, ProgramGenerator};
+
+// Długi test odporności na podział wejścia: losowe programy z ProgramGenerator
+// są rozwijane w całości i ponownie pocięte w losowych miejscach na kawałki.
+// Wynik musi być identyczny.
+//
+// Użycie: cargo run --release --example soak [liczba_programów] [pierwsze_ziarno]
+// Bez argumentów działa do przerwania.
+fn main() {
+    let args: Vec<u64> = std::env::args().skip(1).filter_map(|a| a.parse().ok()).collect();
+    let count = args.first().copied().unwrap_or(u64::MAX);
+    let first = args.get(1).copied().unwrap_or(0);
+
+    for seed in first..first.saturating_add(count) {
+        let program = ProgramGenerator::new(seed).program(5 + (seed % 40) as usize);
+        let whole = expand(&[&program]);
+
+        // miejsca cięcia wyznaczone z ziarna (xorshift), zawsze na granicy znaku
+        let mut x = seed | 1;
+        let mut bounds: Vec<usize> = program
+            .char_indices()
+            .map(|(i, _)| i)
+            .filter(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x % 4 == 0
+            })
+            .collect();
+        bounds.push(program.len());
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for end in bounds {
+            chunks.push(&program[start..end]);
+            start = end;
+        }
+
+        let split = expand(&chunks);
+        if split != whole {
+            eprintln!("ziarno {}: różne wyniki", seed);
+            eprintln!("program: {:?}", program);
+            eprintln!("kawałki: {:?}", chunks);
+            eprintln!("w całości:  {:?}", whole);
+            eprintln!("w kawałkach: {:?}", split);
+            std::process::exit(1);
+        }
+        if (seed - first + 1) % 10_000 == 0 {
+            println!("{} programów bez różnic", seed - first + 1);
+        }
+    }
+}
+
+fn expand(chunks: &[&str]) -> String {
+    let mut vm = GpmVm::new(ControlChars::default(), 50_000);
+    let mut out: String = chunks.iter().map(|c| vm.run(c)).collect();
+    out.push_str(&vm.end());
+    out
+}
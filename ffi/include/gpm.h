@@ -0,0 +1,97 @@
+/*
+ * gpm.h — C interface to gpm-in-rust
+ *
+ * Link against libgpm.a (plus -lpthread -ldl -lm on Linux) or libgpm.so,
+ * both built by the gpm-ffi crate. A handle is not thread safe; use one per thread.
+ *
+ *     GpmHandle *vm = gpm_new(NULL, 50000);
+ *     GpmOutput out;
+ *     if (gpm_run(vm, text, strlen(text), &out) != GPM_OK)
+ *         fprintf(stderr, "%s\n", gpm_last_error(vm));
+ *     fwrite(out.ptr, 1, out.len, stdout);
+ *     gpm_end(vm, &out);
+ *     gpm_free(vm);
+ */
+#ifndef GPM_H
+#define GPM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status of gpm_run and gpm_end */
+#define GPM_OK 0
+/* A monitor was raised; the output (with its report) is still valid. */
+#define GPM_MONITOR 1
+/* A required pointer was NULL. */
+#define GPM_NULL_POINTER 2
+/* The input is not UTF-8. A sequence split across two gpm_run calls is fine. */
+#define GPM_INVALID_UTF8 3
+/* The VM panicked; the handle can only be freed from now on. */
+#define GPM_PANIC 4
+
+/* Control characters as code points. comment is -1 for none. */
+typedef struct GpmControlChars {
+    int32_t open;
+    int32_t close;
+    int32_t def;
+    int32_t arg_sep;
+    int32_t apply;
+    int32_t load_arg;
+    int32_t comment;
+} GpmControlChars;
+
+/*
+ * UTF-8 output owned by the handle, NUL terminated (len excludes the NUL).
+ * Valid until the next call with the same handle.
+ */
+typedef struct GpmOutput {
+    const char *ptr;
+    size_t len;
+} GpmOutput;
+
+typedef struct GpmHandle GpmHandle;
+
+/*
+ * A new VM with a store of mem_size cells. control_chars may be NULL for
+ * the defaults (< > § , ; ~). Returns NULL if a control character is not a
+ * code point, two control characters are the same (the comment character
+ * included, if set) or mem_size cannot hold the machine macros.
+ */
+GpmHandle *gpm_new(const GpmControlChars *control_chars, size_t mem_size);
+
+/*
+ * Expands len bytes of input at input (which may be NULL when len is 0).
+ * Calls may span several gpm_run calls.
+ */
+int gpm_run(GpmHandle *vm, const uint8_t *input, size_t len, GpmOutput *out_buf);
+
+/*
+ * Nonzero on: drop a newline that comes straight after a top-level call.
+ * Off for a new handle.
+ */
+int gpm_set_discard_newline(GpmHandle *vm, int on);
+
+/* End of input: a call or quote still open is reported as monitor 11. */
+int gpm_end(GpmHandle *vm, GpmOutput *out_buf);
+
+/*
+ * The message of the last call that did not return GPM_OK, or NULL.
+ * Valid until the next call with the same handle.
+ */
+const char *gpm_last_error(const GpmHandle *vm);
+
+/* The first monitor raised by the last call (after GPM_MONITOR), or 0. */
+int gpm_last_monitor(const GpmHandle *vm);
+
+/* Frees the handle; NULL is ignored. */
+void gpm_free(GpmHandle *vm);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GPM_H */
@@ -0,0 +1,240 @@
+// gpm-ffi — C interface to GpmVm (declared in include/gpm.h)
+//
+// A C caller gets an opaque GpmHandle from gpm_new and passes it to every
+// other call. Input is UTF-8 of any length; a multi-byte sequence split
+// between two gpm_run calls is put back together. Output stays owned by the
+// handle: out_buf receives a pointer and a length (the text is also NUL
+// terminated) that are valid until the next call with the same handle.
+//
+// Every call but gpm_new and gpm_free returns a status. Anything other than
+// GPM_OK leaves a message for gpm_last_error; GPM_MONITOR also sets
+// gpm_last_monitor. A panic inside the VM is caught, reported as GPM_PANIC,
+// and makes the handle unusable (everything after that fails the same way).
+//
+// The safety contract of each function is the one spelt out in gpm.h.
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::str;
+
+use gpm_in_rust::{Cell, ControlChars, GpmVm};
+
+pub const GPM_OK: c_int = 0;
+// the run went on, but at least one monitor was raised
+pub const GPM_MONITOR: c_int = 1;
+pub const GPM_NULL_POINTER: c_int = 2;
+pub const GPM_INVALID_UTF8: c_int = 3;
+pub const GPM_PANIC: c_int = 4;
+
+// Mirrors ControlChars. `comment` is a code point, or -1 for none.
+#[repr(C)]
+pub struct GpmControlChars {
+    pub open: i32,
+    pub close: i32,
+    pub def: i32,
+    pub arg_sep: i32,
+    pub apply: i32,
+    pub load_arg: i32,
+    pub comment: i32,
+}
+
+#[repr(C)]
+pub struct GpmOutput {
+    pub ptr: *const c_char,
+    pub len: usize,
+}
+
+pub struct GpmHandle {
+    vm: GpmVm,
+    // the last output handed out, NUL terminated
+    output: Vec<u8>,
+    // input bytes that end in the middle of a UTF-8 sequence
+    pending: Vec<u8>,
+    // the message for gpm_last_error, NUL terminated
+    error: Vec<u8>,
+    monitor: c_int,
+    poisoned: bool,
+}
+
+impl GpmHandle {
+    fn fail(&mut self, status: c_int, message: &str) -> c_int {
+        self.error = message.bytes().chain([0]).collect();
+        status
+    }
+
+    fn succeed(&mut self) {
+        self.error.clear();
+        self.monitor = 0;
+    }
+
+    // Runs f on the VM and hands its output to out
+    fn expand(&mut self, out: &mut GpmOutput, f: impl FnOnce(&mut GpmVm) -> String) -> c_int {
+        *out = GpmOutput {
+            ptr: ptr::null(),
+            len: 0,
+        };
+        let vm = &mut self.vm;
+        let result = match self.poisoned {
+            true => Err(()),
+            false => panic::catch_unwind(AssertUnwindSafe(|| f(vm))).map_err(|_| ()),
+        };
+        let Ok(text) = result else {
+            self.poisoned = true;
+            return self.fail(GPM_PANIC, "the VM panicked; the handle can only be freed");
+        };
+
+        self.output = text.into_bytes();
+        self.output.push(0);
+        *out = GpmOutput {
+            ptr: self.output.as_ptr().cast(),
+            len: self.output.len() - 1,
+        };
+
+        match self.vm.take_errors().into_iter().next() {
+            Some(e) => {
+                self.monitor = e.monitor as c_int;
+                self.fail(GPM_MONITOR, &e.to_string())
+            }
+            None => GPM_OK,
+        }
+    }
+}
+
+// A new VM, or NULL if a control character is not a code point, two of them
+// (the comment character included, if set) are the same, or mem_size is
+// below GpmVm::min_mem_size. control_chars may be NULL for the defaults.
+#[no_mangle]
+pub unsafe extern "C" fn gpm_new(
+    control_chars: *const GpmControlChars,
+    mem_size: usize,
+) -> *mut GpmHandle {
+    let cc = match control_chars.as_ref() {
+        None => ControlChars::default(),
+        Some(c) => {
+            let roles = [c.open, c.close, c.def, c.arg_sep, c.apply, c.load_arg];
+            let comment = (c.comment != -1).then_some(c.comment);
+            let mut all: Vec<i32> = roles.iter().chain(&comment).copied().collect();
+            if all.iter().any(|&x| char::from_u32(x as u32).is_none()) {
+                return ptr::null_mut();
+            }
+            // op_start tells the roles apart by character
+            all.sort_unstable();
+            all.dedup();
+            if all.len() != roles.len() + comment.iter().len() {
+                return ptr::null_mut();
+            }
+            ControlChars {
+                open: c.open as Cell,
+                close: c.close as Cell,
+                def: c.def as Cell,
+                arg_sep: c.arg_sep as Cell,
+                apply: c.apply as Cell,
+                load_arg: c.load_arg as Cell,
+                comment: comment.map(|x| x as Cell),
+            }
+        }
+    };
+
+    if mem_size < GpmVm::min_mem_size() {
+        return ptr::null_mut();
+    }
+    let vm = GpmVm::new(cc, mem_size);
+    Box::into_raw(Box::new(GpmHandle {
+        vm,
+        output: Vec::new(),
+        pending: Vec::new(),
+        error: Vec::new(),
+        monitor: 0,
+        poisoned: false,
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn gpm_run(
+    vm: *mut GpmHandle,
+    input: *const u8,
+    len: usize,
+    out_buf: *mut GpmOutput,
+) -> c_int {
+    let Some(handle) = vm.as_mut() else {
+        return GPM_NULL_POINTER;
+    };
+    handle.succeed();
+    let Some(out) = out_buf.as_mut() else {
+        return handle.fail(GPM_NULL_POINTER, "out_buf is NULL");
+    };
+    if input.is_null() && len > 0 {
+        return handle.fail(GPM_NULL_POINTER, "input is NULL");
+    }
+    if len > 0 {
+        handle.pending.extend_from_slice(slice::from_raw_parts(input, len));
+    }
+
+    // whole characters now, the start of a split one for the next call
+    let bytes = std::mem::take(&mut handle.pending);
+    let valid = match str::from_utf8(&bytes) {
+        Ok(_) => bytes.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(e) => {
+            let message = format!("input is not UTF-8: {}", e);
+            return handle.fail(GPM_INVALID_UTF8, &message);
+        }
+    };
+    handle.pending = bytes[valid..].to_vec();
+    let text = str::from_utf8(&bytes[..valid]).unwrap_or_default();
+    handle.expand(out, |vm| vm.run(text))
+}
+
+// GpmVm::set_discard_newline; on is 0 or not.
+#[no_mangle]
+pub unsafe extern "C" fn gpm_set_discard_newline(vm: *mut GpmHandle, on: c_int) -> c_int {
+    let Some(handle) = vm.as_mut() else {
+        return GPM_NULL_POINTER;
+    };
+    handle.succeed();
+    handle.vm.set_discard_newline(on != 0);
+    GPM_OK
+}
+
+// End of input: reports a call or quote left open, like GpmVm::end.
+#[no_mangle]
+pub unsafe extern "C" fn gpm_end(vm: *mut GpmHandle, out_buf: *mut GpmOutput) -> c_int {
+    let Some(handle) = vm.as_mut() else {
+        return GPM_NULL_POINTER;
+    };
+    handle.succeed();
+    let Some(out) = out_buf.as_mut() else {
+        return handle.fail(GPM_NULL_POINTER, "out_buf is NULL");
+    };
+    if !handle.pending.is_empty() {
+        handle.pending.clear();
+        return handle.fail(GPM_INVALID_UTF8, "input ends inside a UTF-8 sequence");
+    }
+    handle.expand(out, |vm| vm.end())
+}
+
+// The message of the last failed call, or NULL after a successful one. Valid
+// until the next call with the same handle.
+#[no_mangle]
+pub unsafe extern "C" fn gpm_last_error(vm: *const GpmHandle) -> *const c_char {
+    match vm.as_ref() {
+        Some(handle) if !handle.error.is_empty() => handle.error.as_ptr().cast(),
+        _ => ptr::null(),
+    }
+}
+
+// The number of the first monitor raised by the last call, or 0.
+#[no_mangle]
+pub unsafe extern "C" fn gpm_last_monitor(vm: *const GpmHandle) -> c_int {
+    vm.as_ref().map_or(0, |handle| handle.monitor)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn gpm_free(vm: *mut GpmHandle) {
+    if !vm.is_null() {
+        drop(Box::from_raw(vm));
+    }
+}
@@ -0,0 +1,92 @@
+/* Built and run by tests/ffi.rs against the static library. */
+#include <stdio.h>
+#include <string.h>
+
+#include "gpm.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                           \
+            failures++;                                               \
+        }                                                             \
+    } while (0)
+
+static int run(GpmHandle *vm, const char *text, GpmOutput *out) {
+    return gpm_run(vm, (const uint8_t *)text, strlen(text), out);
+}
+
+static int same(GpmOutput out, const char *expected) {
+    return out.len == strlen(expected) && memcmp(out.ptr, expected, out.len) == 0;
+}
+
+int main(void) {
+    GpmOutput out;
+
+    /* defaults, a call split across runs and a UTF-8 char split in two */
+    GpmHandle *vm = gpm_new(NULL, 50000);
+    CHECK(vm != NULL);
+    CHECK(run(vm, "\xc2\xa7" "DEF,Suc,<\xc2", &out) == GPM_OK);
+    CHECK(same(out, ""));
+    CHECK(run(vm, "\xa7" "DEC,\xc2\xa7" "BAR,+,\xc2\xa7" "BIN,~1;,\xc2\xa7" "BIN,1;;;>;", &out) == GPM_OK);
+    CHECK(run(vm, "\xc2\xa7" "Suc,41;!", &out) == GPM_OK);
+    CHECK(same(out, "42!"));
+    CHECK(out.ptr[out.len] == '\0');
+    CHECK(gpm_last_error(vm) == NULL);
+
+    /* monitors: status, number and message, output still there */
+    CHECK(run(vm, "a\xc2\xa7" "Nope;b", &out) == GPM_MONITOR);
+    CHECK(gpm_last_monitor(vm) == 7);
+    CHECK(strcmp(gpm_last_error(vm), "MONITOR 7: Undefined name Nope") == 0);
+    CHECK(out.len > 2 && out.ptr[0] == 'a' && out.ptr[out.len - 1] == 'b');
+
+    CHECK(run(vm, "\xc2\xa7" "Suc,", &out) == GPM_OK);
+    CHECK(gpm_end(vm, &out) == GPM_MONITOR);
+    CHECK(gpm_last_monitor(vm) == 11);
+
+    /* bad input */
+    CHECK(run(vm, "\xff", &out) == GPM_INVALID_UTF8);
+    CHECK(gpm_last_error(vm) != NULL);
+    CHECK(gpm_run(vm, NULL, 0, &out) == GPM_OK);
+    CHECK(gpm_run(vm, NULL, 1, &out) == GPM_NULL_POINTER);
+    CHECK(run(vm, "x", NULL) == GPM_NULL_POINTER);
+    CHECK(gpm_run(NULL, (const uint8_t *)"x", 1, &out) == GPM_NULL_POINTER);
+    gpm_free(vm);
+    gpm_free(NULL);
+
+    /* other control characters */
+    GpmControlChars cc = {'[', ']', '$', ',', ';', '#', -1};
+    vm = gpm_new(&cc, 50000);
+    CHECK(vm != NULL);
+    CHECK(run(vm, "$DEF,Twice,[#1#1];$Twice,ab;", &out) == GPM_OK);
+    CHECK(same(out, "abab"));
+    CHECK(gpm_set_discard_newline(vm, 1) == GPM_OK);
+    CHECK(run(vm, "$Twice,c;\nd", &out) == GPM_OK);
+    CHECK(same(out, "ccd"));
+    CHECK(gpm_set_discard_newline(NULL, 1) == GPM_NULL_POINTER);
+    gpm_free(vm);
+
+    /* store too small for the machine macros */
+    CHECK(gpm_new(NULL, 10) == NULL);
+
+    /* two roles on one character, or the comment character on a role */
+    cc.arg_sep = '$';
+    CHECK(gpm_new(&cc, 50000) == NULL);
+    cc.arg_sep = ',';
+    cc.comment = '#';
+    CHECK(gpm_new(&cc, 50000) == NULL);
+    cc.comment = '%';
+    vm = gpm_new(&cc, 50000);
+    CHECK(vm != NULL);
+    gpm_free(vm);
+
+    cc.open = -5;
+    CHECK(gpm_new(&cc, 50000) == NULL);
+
+    if (failures == 0)
+        printf("ok\n");
+    return failures != 0;
+}
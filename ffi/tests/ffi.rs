@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+// Compiles tests/c/ffi_test.c against the static library cargo built for
+// this test run, then runs it. Skipped when there is no C compiler.
+#[test]
+fn c_program_links_and_runs() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::current_exe().unwrap();
+    // target/<profile>/deps/ffi-<hash>; cargo test leaves the library in deps
+    let deps = exe.parent().unwrap();
+    let lib = deps.join("libgpm.a");
+    assert!(lib.is_file(), "no static library at {}", lib.display());
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let binary = deps.join("ffi_test_c");
+    let compiled = Command::new(&cc)
+        .arg(root.join("tests/c/ffi_test.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(&lib)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&binary)
+        .status();
+    let Ok(status) = compiled else {
+        eprintln!("no C compiler ({cc}), skipping");
+        return;
+    };
+    assert!(status.success(), "{cc} failed");
+
+    let run = Command::new(&binary).output().unwrap();
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(run.status.success(), "{stdout}{stderr}");
+    assert_eq!(stdout, "ok\n");
+}
@@ -1,5 +1,6 @@
 pub type Cell = i32;
 // Control characters (GPM default set)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ControlChars {
     pub open: Cell, // begin quote
     pub close: Cell, // end quote
@@ -7,8 +8,10 @@ pub struct ControlChars {
     pub arg_sep: Cell, // argument separator
     pub apply: Cell, // apply / call
     pub load_arg: Cell, // argument reference
+    pub comment: Option<Cell>, // discards the rest of the line (off by default)
 }
 impl ControlChars {
+    #[allow(clippy::should_implement_trait)]
     pub fn default()->Self {
         ControlChars{
             open: '<' as Cell, // begin quote
@@ -17,6 +20,7 @@ impl ControlChars {
             arg_sep: ',' as Cell, // argument separator
             apply: ';' as Cell, // apply / call
             load_arg: '~' as Cell, // argument reference
+            comment: None,
         }
     }
 }
\ No newline at end of file
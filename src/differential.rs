@@ -0,0 +1,105 @@
+// differential.rs — running two engines side by side
+//
+// Both engines get the same input in the same pieces. What is compared is the
+// whole output (less the diagnostic text of each monitor, which only GpmVm
+// writes) and the monitors raised, by number, message and input line.
+// Per-piece output is not compared: GpmVm reacts to an error as soon as it
+// reads it, the reference only once the call around it is complete.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::MacroEngine;
+
+// Characters of output shown on each side of a divergence
+const SHOWN: usize = 40;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Divergence {
+    // The outputs differ from char `offset` on; each side shows what follows.
+    Output {
+        offset: usize,
+        left: String,
+        right: String,
+    },
+    // The `index`th monitor differs (None: that side raised fewer).
+    Monitor {
+        index: usize,
+        left: Option<(u8, String, usize)>,
+        right: Option<(u8, String, usize)>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Output {
+                offset,
+                left,
+                right,
+            } => write!(
+                f,
+                "output differs at char {}:\n  left:  {:?}\n  right: {:?}",
+                offset, left, right
+            ),
+            Divergence::Monitor { index, left, right } => write!(
+                f,
+                "monitor {} differs:\n  left:  {:?}\n  right: {:?}",
+                index, left, right
+            ),
+        }
+    }
+}
+
+// Runs both engines over the pieces of input, then ends them, and returns
+// the first difference (output first, then monitors), if any.
+pub fn first_divergence(
+    left: &mut dyn MacroEngine,
+    right: &mut dyn MacroEngine,
+    pieces: &[&str],
+) -> Option<Divergence> {
+    let (left_out, left_errors) = expand(left, pieces);
+    let (right_out, right_errors) = expand(right, pieces);
+
+    let a: Vec<char> = left_out.chars().collect();
+    let b: Vec<char> = right_out.chars().collect();
+    if a != b {
+        let offset = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+        let shown = |s: &[char]| -> String { s[offset..].iter().take(SHOWN).collect() };
+        return Some(Divergence::Output {
+            offset,
+            left: shown(&a),
+            right: shown(&b),
+        });
+    }
+
+    let index = (0..left_errors.len().max(right_errors.len()))
+        .find(|&k| left_errors.get(k) != right_errors.get(k))?;
+    Some(Divergence::Monitor {
+        index,
+        left: left_errors.get(index).cloned(),
+        right: right_errors.get(index).cloned(),
+    })
+}
+
+// The whole output without diagnostics, and the monitors raised
+fn expand(engine: &mut dyn MacroEngine, pieces: &[&str]) -> (String, Vec<(u8, String, usize)>) {
+    let mut out = String::new();
+    let mut errors = Vec::new();
+    let chunks = pieces.iter().map(|p| Some(*p)).chain([None]);
+    for piece in chunks {
+        let mut text = match piece {
+            Some(p) => engine.run(p),
+            None => engine.end(),
+        };
+        for e in engine.take_errors() {
+            if let Some(at) = text.find(&e.report) {
+                text.replace_range(at..at + e.report.len(), "");
+            }
+            errors.push((e.monitor, e.message, e.line));
+        }
+        out.push_str(&text);
+    }
+    (out, errors)
+}
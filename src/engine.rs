@@ -0,0 +1,53 @@
+// engine.rs — what the two GPM evaluators have in common
+//
+// GpmVm is the machine of Appendix 2; ReferenceGpm is the same language on
+// Strings and HashMaps. MacroEngine lets tests (see differential.rs) drive
+// either of them the same way.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::ReferenceGpm;
+use crate::{GpmError, GpmVm};
+
+pub trait MacroEngine {
+    // Expands the next piece of input; calls may span pieces.
+    fn run(&mut self, input: &str) -> String;
+
+    // Marks the end of input; anything still open is reported.
+    fn end(&mut self) -> String;
+
+    // Monitors raised since the last call, oldest first. The `report` of each
+    // is the diagnostic text the engine wrote to its output (possibly none).
+    fn take_errors(&mut self) -> Vec<GpmError>;
+}
+
+impl MacroEngine for GpmVm {
+    fn run(&mut self, input: &str) -> String {
+        GpmVm::run(self, input)
+    }
+
+    fn end(&mut self) -> String {
+        GpmVm::end(self)
+    }
+
+    fn take_errors(&mut self) -> Vec<GpmError> {
+        GpmVm::take_errors(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl MacroEngine for ReferenceGpm {
+    fn run(&mut self, input: &str) -> String {
+        ReferenceGpm::run(self, input)
+    }
+
+    fn end(&mut self) -> String {
+        ReferenceGpm::end(self)
+    }
+
+    fn take_errors(&mut self) -> Vec<GpmError> {
+        ReferenceGpm::take_errors(self)
+    }
+}
@@ -0,0 +1,28 @@
+use alloc::string::String;
+use core::fmt;
+
+// One monitor report, as raised by the VM (Appendix 2 monitors 1..11, the
+// extension monitors, and ERROR,message; as monitor 15).
+//
+// `message` is the first line of the report without the "MONITOR:" prefix
+// (for ERROR, the user's message); `report` is the full diagnostic text,
+// including the "Current macros are" backtrace when the error is fatal.
+// `source` and `line` tell where the input was being read at the time: "input"
+// for the text given to run, or an included file or pushed text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GpmError {
+    pub monitor: u8,
+    pub message: String,
+    pub report: String,
+    pub source: String,
+    pub line: usize,
+}
+
+impl fmt::Display for GpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MONITOR {}: {}", self.monitor, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GpmError {}
@@ -0,0 +1,267 @@
+// gen.rs — random GPM programs for property and soak testing
+//
+// ProgramGenerator is seeded, so a failing program can be reproduced from its
+// seed alone. Programs use the default control characters and exercise what
+// chunked input has to survive: definitions with quoted bodies, nested calls
+// in argument lists, ~n and ~{n} references, named parameters, and a few of
+// the extension machine macros. Bodies only call macros defined earlier, so
+// every program terminates; some of them raise monitors on purpose (missing
+// arguments, undefined names, stray terminators in bodies), which is fine as
+// long as the run is deterministic. With extensions off, programs keep to
+// the language of Appendix 2, which ReferenceGpm also understands.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+// SplitMix64: tiny, no dependencies, good enough for test input
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..n (n > 0)
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+const WORDS: &[&str] = &["a", "bc", "xyz", "Ala", "kot", " ", "\n", "1", "42", "-", "."];
+
+pub struct ProgramGenerator {
+    rng: Rng,
+    // macros defined so far: (name, number of arguments the body expects)
+    macros: Vec<(String, usize)>,
+    extensions: bool,
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        ProgramGenerator {
+            rng: Rng(seed),
+            macros: Vec::new(),
+            extensions: true,
+        }
+    }
+
+    // Off: no named parameters, ~{n} or ~#, and of the machine macros only
+    // DEF, VAL, UPDATE, BIN, DEC and BAR.
+    pub fn with_extensions(mut self, on: bool) -> Self {
+        self.extensions = on;
+        self
+    }
+
+    // A complete program of about `statements` top-level statements. Each
+    // call starts from an empty set of macros, as a fresh VM would.
+    pub fn program(&mut self, statements: usize) -> String {
+        self.macros.clear();
+        let mut out = String::new();
+        for _ in 0..statements {
+            self.statement(&mut out);
+        }
+        out
+    }
+
+    fn statement(&mut self, out: &mut String) {
+        match self.rng.below(10) {
+            0..=3 => self.definition(out),
+            4..=6 => self.call(out, 2),
+            7 => self.builtin(out, 2),
+            8 => self.quoted(out, 2),
+            _ => self.word(out),
+        }
+    }
+
+    fn word(&mut self, out: &mut String) {
+        out.push_str(WORDS[self.rng.below(WORDS.len())]);
+    }
+
+    fn text(&mut self, out: &mut String) {
+        for _ in 0..1 + self.rng.below(3) {
+            self.word(out);
+        }
+    }
+
+    // <...> with nested quotes and control characters that stay text
+    fn quoted(&mut self, out: &mut String, depth: usize) {
+        out.push('<');
+        for _ in 0..1 + self.rng.below(3) {
+            match self.rng.below(6) {
+                0 if depth > 0 => self.quoted(out, depth - 1),
+                1 => out.push_str([",", ";", "§", "~1"][self.rng.below(4)]),
+                _ => self.word(out),
+            }
+        }
+        out.push('>');
+    }
+
+    fn definition(&mut self, out: &mut String) {
+        let name = format!("M{}", self.macros.len());
+        let arity = self.rng.below(4);
+        let named = self.extensions && arity > 0 && self.rng.chance(30);
+
+        let _ = write!(out, "§DEF,{},<", name);
+        if named {
+            let params: Vec<String> = (1..=arity).map(|k| format!("p{}", k)).collect();
+            let _ = writeln!(out, "~<{}>", params.join(","));
+        }
+        for _ in 0..1 + self.rng.below(5) {
+            match self.rng.below(9) {
+                0..=2 if arity > 0 => {
+                    let k = 1 + self.rng.below(arity);
+                    match self.rng.below(3) {
+                        0 if named => {
+                            let _ = write!(out, "~{{p{}}}", k);
+                        }
+                        1 if self.extensions => {
+                            let _ = write!(out, "~{{{}}}", k);
+                        }
+                        _ => {
+                            let _ = write!(out, "~{}", k);
+                        }
+                    }
+                }
+                3 => out.push_str("~0"),
+                4 if self.extensions && !named => out.push_str("~#"),
+                5 => self.call(out, 1),
+                6 => self.quoted(out, 1),
+                // a separator or terminator for whatever call the result
+                // lands in, or a call the body never finishes
+                7 if self.rng.chance(10) => match self.rng.below(3) {
+                    0 => out.push(','),
+                    1 => out.push(';'),
+                    _ if !self.macros.is_empty() => out.push_str("§M0,"),
+                    _ => self.word(out),
+                },
+                _ => self.word(out),
+            }
+        }
+        out.push_str(">;");
+        self.macros.push((name, arity));
+    }
+
+    // A call of a defined macro (or, now and then, of an undefined one or
+    // with an argument too few)
+    fn call(&mut self, out: &mut String, depth: usize) {
+        if self.rng.chance(3) {
+            out.push_str("§Nope;");
+            return;
+        }
+        if self.macros.is_empty() {
+            return self.text(out);
+        }
+        let (name, arity) = self.macros[self.rng.below(self.macros.len())].clone();
+        let args = if arity > 0 && self.rng.chance(5) {
+            arity - 1
+        } else {
+            arity
+        };
+        out.push('§');
+        out.push_str(&name);
+        for _ in 0..args {
+            out.push(',');
+            self.argument(out, depth);
+        }
+        out.push(';');
+    }
+
+    fn argument(&mut self, out: &mut String, depth: usize) {
+        match self.rng.below(6) {
+            0 if depth > 0 => self.call(out, depth - 1),
+            1 if depth > 0 => self.builtin(out, depth - 1),
+            2 => self.quoted(out, 1),
+            3 => {}
+            _ => self.text(out),
+        }
+    }
+
+    fn builtin(&mut self, out: &mut String, depth: usize) {
+        if !self.extensions {
+            return self.classic_builtin(out);
+        }
+        match self.rng.below(6) {
+            0 => {
+                out.push_str("§UPPER,");
+                self.argument(out, depth);
+                out.push(';');
+            }
+            1 => {
+                out.push_str("§FOREACH,<[~2:~1]>");
+                for _ in 0..self.rng.below(4) {
+                    out.push(',');
+                    self.argument(out, depth);
+                }
+                out.push(';');
+            }
+            2 => {
+                out.push_str("§REPLACE,");
+                self.argument(out, depth);
+                out.push_str(",a,<(a)>;");
+            }
+            3 => {
+                out.push_str("§INDEX,");
+                self.argument(out, depth);
+                out.push_str(",a;");
+            }
+            4 => {
+                out.push_str("§GENSYM,g;");
+            }
+            _ => match self.macros.len() {
+                0 => out.push_str("§DEFINED,M0,y,n;"),
+                n => {
+                    let _ = write!(out, "§VAL,M{};", self.rng.below(n));
+                }
+            },
+        }
+    }
+
+    fn classic_builtin(&mut self, out: &mut String) {
+        match self.rng.below(4) {
+            0 => {
+                let op = ["+", "-", "x", "/", "R"][self.rng.below(5)];
+                let (w, a) = (self.number(), self.number());
+                let _ = write!(out, "§DEC,§BAR,{},§BIN,{};,§BIN,{};;;", op, w, a);
+            }
+            1 => {
+                let _ = write!(out, "§DEC,§BIN,{};;", self.number());
+            }
+            _ => match self.macros.len() {
+                0 => out.push_str("§VAL,M0;"),
+                n => {
+                    let name = format!("M{}", self.rng.below(n));
+                    if self.rng.chance(50) {
+                        // plain text: a body that calls back could loop
+                        let _ = write!(out, "§UPDATE,{},", name);
+                        self.text(out);
+                        out.push(';');
+                    } else {
+                        let _ = write!(out, "§VAL,{};", name);
+                    }
+                }
+            },
+        }
+    }
+
+    // A BIN argument: small, sometimes negative or zero. Kept small on
+    // purpose: its result and BAR's must stay within the chars ReferenceGpm
+    // can carry a number in (see reference.rs).
+    fn number(&mut self) -> String {
+        let n = self.rng.below(200);
+        match self.rng.below(4) {
+            0 => format!("-{}", n),
+            1 => "0".to_string(),
+            _ => n.to_string(),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// golden.rs — golden-file runner for GPM sources
+//
+// Every `*.gpm` under a directory is expanded by a fresh GpmVm (after an
+// optional shared prelude) and the output is compared with the `*.out` file
+// next to it. Mismatches come with a unified diff; in update mode the `.out`
+// files are rewritten instead.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{ControlChars, GpmVm, SandboxResolver};
+
+// Lines of unchanged text around each hunk of a diff
+const CONTEXT: usize = 3;
+
+pub struct GoldenOptions {
+    control_chars: ControlChars,
+    mem_size: usize,
+    prelude: Option<PathBuf>,
+    update: bool,
+}
+
+impl GoldenOptions {
+    // Same meaning as the arguments of GpmVm::new; each source gets its own VM.
+    pub fn new(control_chars: ControlChars, mem_size: usize) -> Self {
+        GoldenOptions {
+            control_chars,
+            mem_size,
+            prelude: None,
+            update: false,
+        }
+    }
+
+    // Text run before every source. Its output is thrown away, so it can be
+    // a plain list of definitions with newlines between them.
+    pub fn with_prelude(mut self, path: impl Into<PathBuf>) -> Self {
+        self.prelude = Some(path.into());
+        self
+    }
+
+    // Write the actual output to the `.out` files instead of comparing.
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GoldenOutcome {
+    Passed,
+    // unified diff from the expected to the actual output
+    Failed(String),
+    // there is no `.out` file (and the run was not updating)
+    Missing,
+    // the `.out` file was written
+    Updated,
+}
+
+#[derive(Debug)]
+pub struct GoldenCase {
+    pub source: PathBuf,
+    pub outcome: GoldenOutcome,
+}
+
+#[derive(Debug, Default)]
+pub struct GoldenReport {
+    pub cases: Vec<GoldenCase>,
+}
+
+impl GoldenReport {
+    // True if no case failed or lacked an expected output
+    pub fn passed(&self) -> bool {
+        self.cases
+            .iter()
+            .all(|c| matches!(c.outcome, GoldenOutcome::Passed | GoldenOutcome::Updated))
+    }
+}
+
+// Runs every `*.gpm` under dir, in path order. INCLUDE paths are resolved
+// against dir. I/O errors (an unreadable source or prelude, a `.out` file
+// that cannot be written) stop the run.
+pub fn run_golden(dir: &Path, options: &GoldenOptions) -> io::Result<GoldenReport> {
+    let prelude = match &options.prelude {
+        Some(path) => fs::read_to_string(path)?,
+        None => String::new(),
+    };
+
+    let mut sources = Vec::new();
+    collect_sources(dir, &mut sources)?;
+    sources.sort();
+
+    let mut report = GoldenReport::default();
+    for source in sources {
+        let text = fs::read_to_string(&source)?;
+        let actual = expand(dir, options, &prelude, &text);
+        let expected_path = source.with_extension("out");
+
+        let outcome = if options.update {
+            fs::write(&expected_path, &actual)?;
+            GoldenOutcome::Updated
+        } else {
+            match fs::read_to_string(&expected_path) {
+                Ok(expected) if expected == actual => GoldenOutcome::Passed,
+                Ok(expected) => {
+                    let from = expected_path.display().to_string();
+                    let to = format!("{} (actual)", expected_path.display());
+                    GoldenOutcome::Failed(unified_diff(&expected, &actual, &from, &to))
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => GoldenOutcome::Missing,
+                Err(e) => return Err(e),
+            }
+        };
+        report.cases.push(GoldenCase { source, outcome });
+    }
+    Ok(report)
+}
+
+fn collect_sources(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, out)?;
+        } else if path.extension().is_some_and(|e| e == "gpm") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Output of one source, including whatever end() reports
+fn expand(dir: &Path, options: &GoldenOptions, prelude: &str, text: &str) -> String {
+    let mut vm = GpmVm::new(options.control_chars, options.mem_size);
+    vm.set_file_resolver(SandboxResolver::new(dir));
+    let _ = vm.run(prelude);
+    let mut out = vm.run(text);
+    out.push_str(&vm.end());
+    out
+}
+
+// Line diff in the unified format of `diff -u`, with from and to as the file
+// names. Empty if the texts are equal.
+pub fn unified_diff(expected: &str, actual: &str, from: &str, to: &str) -> String {
+    let a: Vec<&str> = expected.split_inclusive('\n').collect();
+    let b: Vec<&str> = actual.split_inclusive('\n').collect();
+    let ops = edit_script(&a, &b);
+    if ops.iter().all(|op| matches!(op, Op::Same(..))) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", from, to);
+
+    // hunks: changes at most 2 * CONTEXT unchanged lines apart, plus context
+    let changes: Vec<usize> = (0..ops.len())
+        .filter(|&k| !matches!(ops[k], Op::Same(..)))
+        .collect();
+    let mut k = 0;
+    while k < changes.len() {
+        let mut last = k;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[k].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(ops.len());
+        write_hunk(&mut out, &ops[start..end], &a, &b);
+        k = last + 1;
+    }
+    out
+}
+
+// One step of the edit script, with line indexes into the old and new text
+enum Op {
+    Same(usize, usize),
+    Delete(usize, usize),
+    Insert(usize, usize),
+}
+
+// Longest common subsequence of the lines, walked from the start
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let (n, m) = (a.len(), b.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            ops.push(Op::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Delete(i, j));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+fn write_hunk(out: &mut String, ops: &[Op], a: &[&str], b: &[&str]) {
+    let (i0, j0) = match ops[0] {
+        Op::Same(i, j) | Op::Delete(i, j) | Op::Insert(i, j) => (i, j),
+    };
+    let old_len = ops.iter().filter(|op| !matches!(op, Op::Insert(..))).count();
+    let new_len = ops.iter().filter(|op| !matches!(op, Op::Delete(..))).count();
+    // an empty range is numbered after the line it follows
+    let range = |start: usize, len: usize| {
+        let first = if len == 0 { start } else { start + 1 };
+        format!("{},{}", first, len)
+    };
+    out.push_str(&format!("@@ -{} +{} @@\n", range(i0, old_len), range(j0, new_len)));
+
+    for op in ops {
+        let (mark, line) = match *op {
+            Op::Same(i, _) => (' ', a[i]),
+            Op::Delete(i, _) => ('-', a[i]),
+            Op::Insert(_, j) => ('+', b[j]),
+        };
+        out.push(mark);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
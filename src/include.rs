@@ -0,0 +1,136 @@
+// include.rs — file resolution for the INCLUDE machine macro
+//
+// INCLUDE,path; never touches the file system directly: the path is handed to a
+// FileResolver, which decides what the name means and whether it may be read.
+// SandboxResolver is the stock implementation: every path is looked up relative
+// to a root directory (then to each search directory under that root), and any
+// path that would leave the root is rejected. It needs the `std` feature; the
+// trait does not.
+
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::{Component, Path, PathBuf};
+
+// A file handed back by a resolver.
+// `name` identifies the file for cycle detection, so two spellings of the same
+// file must produce the same name (SandboxResolver uses the canonical path).
+pub struct IncludeFile {
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IncludeError {
+    OutsideRoot,
+    NotFound,
+    Unreadable(String),
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncludeError::OutsideRoot => write!(f, "path escapes the include root"),
+            IncludeError::NotFound => write!(f, "file not found"),
+            IncludeError::Unreadable(e) => write!(f, "file cannot be read: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IncludeError {}
+
+pub trait FileResolver {
+    fn resolve(&mut self, path: &str) -> Result<IncludeFile, IncludeError>;
+}
+
+#[cfg(feature = "std")]
+pub struct SandboxResolver {
+    root: PathBuf,
+    search_path: Vec<PathBuf>,
+}
+
+#[cfg(feature = "std")]
+impl SandboxResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        SandboxResolver {
+            root: root.into(),
+            search_path: Vec::new(),
+        }
+    }
+
+    // Search directories are relative to the root and are tried in order after
+    // the root itself.
+    pub fn with_search_path<I, D>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = D>,
+        D: Into<PathBuf>,
+    {
+        self.search_path.extend(dirs.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn search_path(&self) -> &[PathBuf] {
+        &self.search_path
+    }
+
+    // Purely lexical: removes `.` and folds `..`, returning None when `..`
+    // climbs above the first component (i.e. above the root).
+    fn normalize(rel: &Path) -> Option<PathBuf> {
+        let mut out = PathBuf::new();
+        for comp in rel.components() {
+            match comp {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !out.pop() {
+                        return None;
+                    }
+                }
+                Component::Normal(c) => out.push(c),
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+        Some(out)
+    }
+}
+
+#[cfg(feature = "std")]
+impl FileResolver for SandboxResolver {
+    fn resolve(&mut self, path: &str) -> Result<IncludeFile, IncludeError> {
+        let root = fs::canonicalize(&self.root)
+            .map_err(|e| IncludeError::Unreadable(format!("{}: {}", self.root.display(), e)))?;
+
+        let dirs = std::iter::once(PathBuf::new()).chain(self.search_path.iter().cloned());
+        for dir in dirs {
+            // lexical check first, so that a missing file outside the root is
+            // still reported as an escape and not as "not found"
+            let rel = Self::normalize(&dir.join(path)).ok_or(IncludeError::OutsideRoot)?;
+            let candidate = root.join(rel);
+            if !candidate.is_file() {
+                continue;
+            }
+
+            // symlinks may still point outside
+            let real = fs::canonicalize(&candidate)
+                .map_err(|e| IncludeError::Unreadable(e.to_string()))?;
+            if !real.starts_with(&root) {
+                return Err(IncludeError::OutsideRoot);
+            }
+
+            let text =
+                fs::read_to_string(&real).map_err(|e| IncludeError::Unreadable(e.to_string()))?;
+            return Ok(IncludeFile {
+                name: real.display().to_string(),
+                text,
+            });
+        }
+
+        Err(IncludeError::NotFound)
+    }
+}
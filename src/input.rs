@@ -0,0 +1,106 @@
+// input.rs — the input stream of GpmVm as a stack of named sources
+//
+// The base source is the text given to run, chunk after chunk; its line count
+// carries on across chunks. Text pushed on top (an INCLUDE file, text from
+// the host) is read first. A pushed source stays on the stack until the
+// character after its last one is asked for, so INCLUDE as the very last
+// call of a file still sees that file as active.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// Name of the base source in positions
+pub const BASE_NAME: &str = "input";
+
+struct Source {
+    name: String,
+    // read for an INCLUDE (not pushed by the host)
+    included: bool,
+    text: Vec<char>,
+    // next char to read
+    pos: usize,
+    // line of that char, from 1
+    line: usize,
+}
+
+impl Source {
+    fn new(name: &str, included: bool, text: &str) -> Self {
+        Source {
+            name: name.to_string(),
+            included,
+            text: text.chars().collect(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = *self.text.get(self.pos)?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+}
+
+pub struct InputStack {
+    base: Source,
+    pushed: Vec<Source>,
+}
+
+impl InputStack {
+    pub fn new() -> Self {
+        InputStack {
+            base: Source::new(BASE_NAME, false, ""),
+            pushed: Vec::new(),
+        }
+    }
+
+    // The next chunk of the base source, read after what is left of it.
+    pub fn feed(&mut self, text: &str) {
+        self.base.text.drain(..self.base.pos);
+        self.base.pos = 0;
+        self.base.text.extend(text.chars());
+    }
+
+    // A source read before everything else on the stack.
+    pub fn push(&mut self, name: &str, text: &str) {
+        self.pushed.push(Source::new(name, false, text));
+    }
+
+    // push, for the file of an INCLUDE
+    pub fn include(&mut self, name: &str, text: &str) {
+        self.pushed.push(Source::new(name, true, text));
+    }
+
+    pub fn next(&mut self) -> Option<char> {
+        while let Some(top) = self.pushed.last_mut() {
+            if let Some(c) = top.next() {
+                return Some(c);
+            }
+            self.pushed.pop();
+        }
+        self.base.next()
+    }
+
+    // Is a file of this name still being read for an INCLUDE? (Text the
+    // host pushed may have any name.)
+    pub fn is_included(&self, name: &str) -> bool {
+        self.pushed.iter().any(|s| s.included && s.name == name)
+    }
+
+    // Name and line of the source the next character comes from (the
+    // innermost one still open).
+    pub fn position(&self) -> (&str, usize) {
+        let top = self.pushed.last().unwrap_or(&self.base);
+        (&top.name, top.line)
+    }
+
+    // Drops all unread text; the base keeps its line count.
+    pub fn discard(&mut self) {
+        self.pushed.clear();
+        self.base.text.clear();
+        self.base.pos = 0;
+    }
+}
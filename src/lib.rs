@@ -1,6 +1,43 @@
+// Without the default `std` feature the crate is no_std + alloc: the VM,
+// the prelude and the test tooling that needs nothing but allocation.
+// File access (SandboxResolver, the golden runner) and the HashMap-based
+// reference evaluator come with `std`. The C API is the gpm-ffi crate.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 mod pc;
 mod control_chars;
+mod differential;
+mod engine;
+mod error;
+mod gen;
+#[cfg(feature = "std")]
+mod golden;
+mod include;
+mod input;
+mod prelude;
+#[cfg(feature = "std")]
+mod reference;
+mod sink;
 mod vm;
 
 pub use control_chars::{Cell, ControlChars};
+pub use differential::{first_divergence, Divergence};
+pub use engine::MacroEngine;
+pub use error::GpmError;
+pub use gen::ProgramGenerator;
+#[cfg(feature = "std")]
+pub use golden::{
+    run_golden, unified_diff, GoldenCase, GoldenOptions, GoldenOutcome, GoldenReport,
+};
+pub use include::{FileResolver, IncludeError, IncludeFile};
+#[cfg(feature = "std")]
+pub use include::SandboxResolver;
+pub use prelude::{prelude_for, PRELUDE};
+#[cfg(feature = "std")]
+pub use reference::ReferenceGpm;
+#[cfg(feature = "std")]
+pub use sink::IoSink;
+pub use sink::{FnSink, OutputSink};
 pub use vm::GpmVm;
@@ -1,4 +1,119 @@
-fn main() {
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use gpm_in_rust::{run_golden, ControlChars, GoldenOptions, GoldenOutcome};
+
+const USAGE: &str = "użycie: gpm-in-rust golden KATALOG [--prelude PLIK] [--mem N] [--update]
+    [--open Z] [--close Z] [--def Z] [--arg-sep Z] [--apply Z] [--load-arg Z] [--comment Z]
+(Z: jeden znak sterujący zamiast domyślnego)";
+
+fn main() -> ExitCode {
     // Ten pakiet jest głównie biblioteką.
     // Przykłady użycia są w katalogu `examples/`, a dawny harness przeniesiono do `tests/`.
+    // Jedyne polecenie to runner plików wzorcowych (*.gpm -> *.out).
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("golden") => golden(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn golden(args: &[String]) -> ExitCode {
+    let mut dir: Option<PathBuf> = None;
+    let mut prelude: Option<PathBuf> = None;
+    let mut mem_size = 50_000;
+    let mut update = false;
+    let mut cc = ControlChars::default();
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--update" => update = true,
+            "--prelude" => match it.next() {
+                Some(p) => prelude = Some(PathBuf::from(p)),
+                None => return usage(),
+            },
+            "--mem" => match it.next().and_then(|n| n.parse().ok()) {
+                Some(n) => mem_size = n,
+                None => return usage(),
+            },
+            "--open" | "--close" | "--def" | "--arg-sep" | "--apply" | "--load-arg" | "--comment" => {
+                let Some(c) = it.next().and_then(|z| one_char(z)) else {
+                    return usage();
+                };
+                match arg.as_str() {
+                    "--open" => cc.open = c,
+                    "--close" => cc.close = c,
+                    "--def" => cc.def = c,
+                    "--arg-sep" => cc.arg_sep = c,
+                    "--apply" => cc.apply = c,
+                    "--load-arg" => cc.load_arg = c,
+                    _ => cc.comment = Some(c),
+                }
+            }
+            _ if dir.is_none() && !arg.starts_with("--") => dir = Some(PathBuf::from(arg)),
+            _ => return usage(),
+        }
+    }
+    let Some(dir) = dir else {
+        return usage();
+    };
+    let mut roles = vec![cc.open, cc.close, cc.def, cc.arg_sep, cc.apply, cc.load_arg];
+    roles.extend(cc.comment);
+    let count = roles.len();
+    roles.sort_unstable();
+    roles.dedup();
+    if roles.len() != count {
+        eprintln!("błąd: każdy znak sterujący musi być inny");
+        return ExitCode::FAILURE;
+    }
+
+    let mut options = GoldenOptions::new(cc, mem_size).with_update(update);
+    if let Some(p) = prelude {
+        options = options.with_prelude(p);
+    }
+
+    let report = match run_golden(&dir, &options) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("błąd: {}: {}", dir.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for case in &report.cases {
+        let name = case.source.display();
+        match &case.outcome {
+            GoldenOutcome::Passed => println!("{:<9}{}", "ok", name),
+            GoldenOutcome::Updated => println!("{:<9}{}", "zapisano", name),
+            GoldenOutcome::Missing => println!("{:<9}{} (nie ma pliku .out)", "BRAK", name),
+            GoldenOutcome::Failed(diff) => {
+                println!("{:<9}{}", "BŁĄD", name);
+                print!("{}", diff);
+            }
+        }
+    }
+
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// Argument flagi znaku sterującego: dokładnie jeden znak
+fn one_char(arg: &str) -> Option<i32> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c as i32),
+        _ => None,
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::FAILURE
 }
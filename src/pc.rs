@@ -1,3 +1,5 @@
+// Machine macro states are named as the macros are spelt (Appendix 2)
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Pc {
     // Main cycle
@@ -5,12 +7,14 @@ pub enum Pc {
     Copy,
     Scan,
     Q2,
+    Comment,
 
     // Warning character actions
     Fn,
     NextItem,
     Apply,
     LoadArg,
+    ArgRef,
     EndFn,
     Exit,
 
@@ -22,6 +26,26 @@ pub enum Pc {
     DEC,
     BAR,
 
+    // Extension machine macros
+    INCLUDE,
+    DIVERT,
+    UNDIVERT,
+    UNDEF,
+    DEFINED,
+    ERROR,
+    WARN,
+    UPPER,
+    LOWER,
+    CLASS,
+    INDEX,
+    REPLACE,
+    TRANSLIT,
+    VAR,
+    GENSYM,
+    CHANGECC,
+    FOREACH,
+    GDEF,
+
     // Monitors
     Monitor(u8),
 
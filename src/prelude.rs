@@ -0,0 +1,76 @@
+// prelude.rs — the classic macros of Strachey's paper as a bundled library
+//
+// prelude.gpm is written with the default control characters, one definition
+// per line (the newlines are output and thrown away when it is loaded):
+//
+//   Suc,d;            successor of a digit, by selecting from a list (0-9)
+//   Eq,a,b,then,else; conditional by name lookup: a and b are defined with
+//                     the two branches, and the later definition of a wins
+//                     only if the names are equal. The chosen text is not
+//                     rescanned; to evaluate a branch select a macro name:
+//                     §§Eq,x,y,Then,Else;;
+//   Add,Sub,Mul,a,b;  decimal arithmetic through BIN, BAR and DEC
+//   NewCounter,c;     defines c as a counter at 0, with room for ten digits
+//                     so that UPDATE can grow it in place
+//   Next,c;           increments counter c and yields its new value
+//   Fact,n;           recursive factorial, choosing between Fact0 and FactN
+//                     with Eq as above
+//
+// Counters made by NewCounter and definitions made while a prelude macro runs
+// follow the usual rules: they are local when the call is inside an argument
+// list.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{Cell, ControlChars};
+
+pub const PRELUDE: &str = include_str!("prelude.gpm");
+
+// PRELUDE with each default control character replaced by the one cc uses
+// for the same role, and the names of the definitions left out of it. A
+// definition that would then contain cc's comment character is left out, as
+// the comment would cut it short. The ones left out per comment character:
+//
+//   '+'             Add
+//   '-'             Sub (Fact still needs it, through FactN)
+//   'x'             Mul and Next
+//   '1', 'D', 'E'   all of them ("§DEF" is in every line)
+//   or 'F'
+//   another digit   the ones holding it (e.g. '0': Suc, NewCounter, Fact
+//   or letter       and Fact0), as the second part of the result says
+//   '\n'            none; the definitions are run together instead
+pub fn prelude_for(cc: ControlChars) -> (String, Vec<&'static str>) {
+    let default = ControlChars::default();
+    let roles = [
+        (default.open, cc.open),
+        (default.close, cc.close),
+        (default.def, cc.def),
+        (default.arg_sep, cc.arg_sep),
+        (default.apply, cc.apply),
+        (default.load_arg, cc.load_arg),
+    ];
+    let translate = |c: char| {
+        let x = c as Cell;
+        let x = roles.iter().find(|(d, _)| *d == x).map_or(x, |&(_, to)| to);
+        char::from_u32(x as u32).unwrap_or(c)
+    };
+    let is_comment = |c: char| cc.comment == Some(c as Cell);
+
+    let mut out = String::new();
+    let mut omitted = Vec::new();
+    for definition in PRELUDE.lines() {
+        let line: String = definition.chars().map(translate).collect();
+        if line.chars().any(is_comment) {
+            // every line is "§DEF,name,<...>;"
+            omitted.extend(definition.split(',').nth(1));
+            continue;
+        }
+        out.push_str(&line);
+        // the newlines are only there for reading
+        if !is_comment('\n') {
+            out.push('\n');
+        }
+    }
+    (out, omitted)
+}
@@ -0,0 +1,572 @@
+// reference.rs — a second, high-level GPM evaluator for differential testing
+//
+// ReferenceGpm has the semantics of the Appendix 2 machine without its store:
+// text is a String, definitions live in a stack of HashMap scopes (the global
+// one plus one per call in progress), and macro bodies are expanded by plain
+// recursion. It knows quoting, ~n, local definitions, the six machine macros
+// DEF, VAL, UPDATE, BIN, DEC and BAR, and monitors 1-5 and 7-11 with the
+// messages and recovery of GpmVm. It writes no diagnostics, so the `report`
+// of its errors is empty.
+//
+// None of the extensions are there (named parameters, ~{n} and ~#, the
+// extension machine macros, comment characters), so the two engines are
+// only comparable on classic programs. Both engines wrap round when a number
+// overflows a cell. Three differences are deliberate, and the programs of
+// `gen` keep away from them:
+// - a definition made while an argument is being collected leaves its entry
+//   in that argument's text in GpmVm; here it leaves nothing;
+// - a number made by BIN travels as a single char: the char with that code
+//   when there is one, a char of the supplementary private use area when it
+//   is negative. Output shows it the way GpmVm outputs a cell. Numbers
+//   from 983040 (U+F0000) up, below -65534 or in the surrogates, as an
+//   overflow mostly gives, do not come back the same here;
+// - DEC and BAR take an empty or missing argument as 0 here. GpmVm reads
+//   whatever cell follows in the store, e.g. the Marker (-1048576) for
+//   `§DEC,;`.
+
+use std::collections::HashMap;
+
+use crate::{Cell, ControlChars, GpmError};
+
+// Negative numbers -1, -2, ... are the chars from here on
+const NEGATIVE_BASE: u32 = 0xF0000;
+const NEGATIVE_LAST: u32 = 0xFFFFD;
+
+// Calls in progress at most; where GpmVm runs out of store, this runs out of
+// depth (monitor 11 either way)
+const MAX_DEPTH: usize = 1000;
+
+#[derive(Clone, Copy)]
+enum Machine {
+    Def,
+    Val,
+    Update,
+    Bin,
+    Dec,
+    Bar,
+}
+
+#[derive(Clone)]
+enum Value {
+    // UPDATE may not make the text longer than it was when defined
+    Text { text: String, capacity: usize },
+    Machine(Machine),
+}
+
+struct Definition {
+    // order of definition; the newest visible definition of a name wins
+    seq: u64,
+    value: Value,
+}
+
+// Every name maps to its definitions in this scope, newest last
+type Scope = HashMap<String, Vec<Definition>>;
+
+// The expansion was given up after a fatal monitor (already recorded)
+struct Abandoned;
+
+type Flow<T> = Result<T, Abandoned>;
+
+#[derive(PartialEq, Eq)]
+enum Stop {
+    // the terminator of the call being collected
+    Apply,
+    // the end of a macro body
+    End,
+}
+
+// Where a scan reads from: the input, or a macro body with its arguments
+enum Source<'a> {
+    Input,
+    Body {
+        text: &'a [char],
+        pos: usize,
+        args: &'a [String],
+    },
+}
+
+// Where a scan writes to: the items of the call being collected, or a single
+// item that is the output (or the current argument of an outer call)
+struct Target<'a> {
+    items: &'a mut Vec<String>,
+    collecting: bool,
+}
+
+impl Target<'_> {
+    fn push(&mut self, c: char) {
+        if let Some(item) = self.items.last_mut() {
+            item.push(c);
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if let Some(item) = self.items.last_mut() {
+            item.push_str(s);
+        }
+    }
+}
+
+pub struct ReferenceGpm {
+    open: char,
+    close: char,
+    def: char,
+    arg_sep: char,
+    apply: char,
+    load_arg: char,
+
+    // input not yet consumed; a call is only started once it is complete
+    input: Vec<char>,
+    pos: usize,
+    // input line of input[0], from 1
+    line: usize,
+    // depth of a quote open at the top level
+    quote: usize,
+
+    // scopes[0] is global, scopes[k] belongs to calls[k - 1]
+    scopes: Vec<Scope>,
+    // for every call in progress: is it still collecting its arguments?
+    calls: Vec<bool>,
+    seq: u64,
+
+    errors: Vec<GpmError>,
+}
+
+impl ReferenceGpm {
+    pub fn new(control_chars: ControlChars) -> Self {
+        let ch = |c: Cell| char::from_u32(c as u32).unwrap_or('\u{FFFD}');
+        let mut global = Scope::new();
+        for (name, m) in [
+            ("DEF", Machine::Def),
+            ("VAL", Machine::Val),
+            ("UPDATE", Machine::Update),
+            ("BIN", Machine::Bin),
+            ("DEC", Machine::Dec),
+            ("BAR", Machine::Bar),
+        ] {
+            global.insert(
+                name.to_string(),
+                vec![Definition {
+                    seq: 0,
+                    value: Value::Machine(m),
+                }],
+            );
+        }
+        ReferenceGpm {
+            open: ch(control_chars.open),
+            close: ch(control_chars.close),
+            def: ch(control_chars.def),
+            arg_sep: ch(control_chars.arg_sep),
+            apply: ch(control_chars.apply),
+            load_arg: ch(control_chars.load_arg),
+            input: Vec::new(),
+            pos: 0,
+            line: 1,
+            quote: 0,
+            scopes: vec![global],
+            calls: Vec::new(),
+            seq: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    // Every monitor raised since the last take_errors, oldest first.
+    pub fn errors(&self) -> &[GpmError] {
+        &self.errors
+    }
+
+    pub fn take_errors(&mut self) -> Vec<GpmError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // Same contract as GpmVm::run: input may stop anywhere, and the rest of
+    // a call or quote comes with the next run.
+    pub fn run(&mut self, input: &str) -> String {
+        self.input.extend(input.chars());
+        let mut out = vec![String::new()];
+
+        while let Some(&c) = self.input.get(self.pos) {
+            if self.quote > 0 {
+                self.pos += 1;
+                if c == self.open {
+                    self.quote += 1;
+                } else if c == self.close {
+                    self.quote -= 1;
+                    if self.quote == 0 {
+                        continue;
+                    }
+                }
+                out[0].push(c);
+            } else if c == self.open {
+                self.pos += 1;
+                self.quote = 1;
+            } else if c == self.close {
+                // Finish: the rest of this input is dropped
+                self.pos += 1;
+                self.consume();
+                self.input.clear();
+                break;
+            } else if c == self.def {
+                if !self.call_complete() {
+                    break;
+                }
+                self.pos += 1;
+                let floor = self.seq;
+                let mut target = Target {
+                    items: &mut out,
+                    collecting: false,
+                };
+                if self.call(&mut Source::Input, &mut target).is_err() {
+                    self.abandon(floor);
+                }
+            } else {
+                self.pos += 1;
+                out[0].push(c);
+            }
+        }
+
+        self.consume();
+        out.swap_remove(0)
+            .chars()
+            .map(|c| match c as u32 {
+                NEGATIVE_BASE..=NEGATIVE_LAST => '\u{FFFD}',
+                _ => c,
+            })
+            .collect()
+    }
+
+    // Input cut off inside a call or a quote is monitor 11, as in GpmVm.
+    pub fn end(&mut self) -> String {
+        if self.quote > 0 || !self.input.is_empty() {
+            // GpmVm has read what is left by now
+            self.pos = self.input.len();
+            self.note(11, "Irremediable error".to_string());
+        }
+        self.consume();
+        self.quote = 0;
+        String::new()
+    }
+
+    // Drops the input read so far, counting its lines
+    fn consume(&mut self) {
+        self.line += self.input.drain(..self.pos).filter(|&c| c == '\n').count();
+        self.pos = 0;
+    }
+
+    // Does the input hold the whole call starting at pos? A stray close
+    // counts as the end too, since that is where the call fails.
+    fn call_complete(&self) -> bool {
+        let (mut depth, mut quote) = (0usize, 0usize);
+        for &c in &self.input[self.pos..] {
+            if quote > 0 {
+                if c == self.open {
+                    quote += 1;
+                } else if c == self.close {
+                    quote -= 1;
+                }
+            } else if c == self.open {
+                quote = 1;
+            } else if c == self.def {
+                depth += 1;
+            } else if c == self.apply {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            } else if c == self.close {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Monitor 11 recovery: the calls are dropped, with every definition made
+    // since the top-level call started, and reading goes on with the input.
+    fn abandon(&mut self, floor: u64) {
+        self.calls.clear();
+        self.scopes.truncate(1);
+        self.scopes[0].retain(|_, defs| {
+            defs.retain(|d| d.seq <= floor);
+            !defs.is_empty()
+        });
+    }
+
+    // messages are trimmed the way GpmVm trims the first line of a report
+    fn note(&mut self, monitor: u8, message: String) {
+        let read = &self.input[..self.pos];
+        self.errors.push(GpmError {
+            monitor,
+            message: message.trim().to_string(),
+            report: String::new(),
+            source: crate::input::BASE_NAME.to_string(),
+            line: self.line + read.iter().filter(|&&c| c == '\n').count(),
+        });
+    }
+
+    fn fail<T>(&mut self, monitor: u8, message: String) -> Flow<T> {
+        self.note(monitor, message);
+        Err(Abandoned)
+    }
+
+    fn next(&mut self, src: &mut Source) -> Option<char> {
+        match src {
+            Source::Input => {
+                let c = self.input.get(self.pos).copied();
+                self.pos += c.is_some() as usize;
+                c
+            }
+            Source::Body { text, pos, .. } => {
+                let c = text.get(*pos).copied();
+                *pos += c.is_some() as usize;
+                c
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Definition> {
+        self.scopes
+            .iter()
+            .filter_map(|s| s.get(name)?.last())
+            .max_by_key(|d| d.seq)
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Definition> {
+        self.scopes
+            .iter_mut()
+            .filter_map(|s| s.get_mut(name)?.last_mut())
+            .max_by_key(|d| d.seq)
+    }
+
+    // Reads src into target up to the end of the source or, when
+    // own_call, the terminator of the call src started.
+    fn scan(&mut self, src: &mut Source, target: &mut Target, own_call: bool) -> Flow<Stop> {
+        while let Some(c) = self.next(src) {
+            if c == self.open {
+                self.quoted(src, target);
+            } else if c == self.def {
+                self.call(src, target)?;
+            } else if c == self.arg_sep && target.collecting {
+                target.items.push(String::new());
+            } else if c == self.apply && own_call {
+                return Ok(Stop::Apply);
+            } else if c == self.apply && target.collecting {
+                // a body ending an argument list it did not start
+                let name = match src {
+                    Source::Body { args, .. } => args[0].clone(),
+                    Source::Input => String::new(),
+                };
+                self.note(1, format!("Unmatched semicolon in definition of {}", name));
+                target.push(c);
+            } else if c == self.load_arg {
+                self.argument(src, target)?;
+            } else if c == self.close {
+                return self.fail(8, "Unmatched >. Probably machine error.".to_string());
+            } else {
+                target.push(c);
+            }
+        }
+        Ok(Stop::End)
+    }
+
+    // The rest of a quote, without its closing character
+    fn quoted(&mut self, src: &mut Source, target: &mut Target) {
+        let mut depth = 1;
+        while let Some(c) = self.next(src) {
+            if c == self.open {
+                depth += 1;
+            } else if c == self.close {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+            target.push(c);
+        }
+    }
+
+    fn argument(&mut self, src: &mut Source, target: &mut Target) -> Flow<()> {
+        let args = match src {
+            Source::Body { args, .. } => *args,
+            Source::Input => {
+                // the input is only scanned inside an argument list
+                let name = item_name(target.items);
+                self.note(2, format!("Unquoted tilde in argument list of {}", name));
+                target.push(self.load_arg);
+                return Ok(());
+            }
+        };
+        let digit = self.next(src);
+        let n = digit.map_or(-1, |d| d as i64 - '0' as i64);
+        if n < 0 {
+            let message = format!("Impossible argument number in definition of {}", args[0]);
+            return self.fail(3, message);
+        }
+        match args.get(n as usize) {
+            Some(arg) => {
+                target.push_str(arg);
+                Ok(())
+            }
+            None => {
+                let digit = digit.unwrap_or_default();
+                self.fail(4, format!("No argument {}", digit))
+            }
+        }
+    }
+
+    // A call whose warning character was just read from src; its result goes
+    // to target.
+    fn call(&mut self, src: &mut Source, target: &mut Target) -> Flow<()> {
+        if self.calls.len() >= MAX_DEPTH {
+            return self.fail(11, "Irremediable error".to_string());
+        }
+        let mut items = vec![String::new()];
+        self.calls.push(true);
+        self.scopes.push(Scope::new());
+        let result = self.collect_and_apply(src, &mut items, target);
+        self.calls.pop();
+        self.scopes.pop();
+        result
+    }
+
+    fn collect_and_apply(
+        &mut self,
+        src: &mut Source,
+        items: &mut Vec<String>,
+        target: &mut Target,
+    ) -> Flow<()> {
+        let mut args = Target {
+            items,
+            collecting: true,
+        };
+        if self.scan(src, &mut args, true)? == Stop::End {
+            // a body ended inside a call it started: apply it anyway
+            let Source::Body { .. } = src else {
+                let message = "Terminator in input stream. Probably machine error.".to_string();
+                return self.fail(5, message);
+            };
+            let name = item_name(items);
+            self.note(5, format!("Terminator in argument list for {}", name));
+        }
+        if let Some(collecting) = self.calls.last_mut() {
+            *collecting = false;
+        }
+        self.apply(items, target)
+    }
+
+    fn apply(&mut self, items: &[String], target: &mut Target) -> Flow<()> {
+        let value = match self.lookup(&items[0]) {
+            Some(d) => d.value.clone(),
+            None => return self.fail(7, format!("Undefined name {}", items[0])),
+        };
+        match value {
+            Value::Machine(m) => self.machine(m, items, target),
+            Value::Text { text, .. } => {
+                let body: Vec<char> = text.chars().collect();
+                let mut src = Source::Body {
+                    text: &body,
+                    pos: 0,
+                    args: items,
+                };
+                self.scan(&mut src, target, false).map(|_| ())
+            }
+        }
+    }
+
+    fn machine(&mut self, m: Machine, items: &[String], target: &mut Target) -> Flow<()> {
+        let arg = |k: usize| items.get(k).map_or("", String::as_str);
+        // first char of an argument, as a number
+        let word = |k: usize| arg(k).chars().next().map_or(0, word_of);
+
+        match m {
+            Machine::Def => {
+                // local to the innermost call still collecting its arguments
+                let owner = self.calls.iter().rposition(|&c| c).map_or(0, |k| k + 1);
+                self.seq += 1;
+                let text = arg(2).to_string();
+                let capacity = text.chars().count();
+                self.scopes[owner]
+                    .entry(arg(1).to_string())
+                    .or_default()
+                    .push(Definition {
+                        seq: self.seq,
+                        value: Value::Text { text, capacity },
+                    });
+            }
+            Machine::Val => match self.lookup(arg(1)).map(|d| &d.value) {
+                Some(Value::Text { text, .. }) => {
+                    let text = text.clone();
+                    target.push_str(&text);
+                }
+                Some(Value::Machine(_)) => {}
+                None => return self.fail(7, format!("Undefined name {}", arg(1))),
+            },
+            Machine::Update => {
+                let new = arg(2);
+                match self.lookup_mut(arg(1)).map(|d| &mut d.value) {
+                    Some(Value::Text { text, capacity }) => {
+                        if new.chars().count() > *capacity {
+                            return self.fail(9, format!("Update argument too long for {}", arg(1)));
+                        }
+                        *text = new.to_string();
+                    }
+                    Some(Value::Machine(_)) => {}
+                    None => return self.fail(7, format!("Undefined name {}", arg(1))),
+                }
+            }
+            Machine::Bin => {
+                let text = arg(1);
+                let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+                let mut w: Cell = 0;
+                for c in digits.chars() {
+                    match c.to_digit(10) {
+                        Some(d) => w = w.wrapping_mul(10).wrapping_add(d as Cell),
+                        None => return self.fail(10, "Non-digit in number".to_string()),
+                    }
+                }
+                target.push(word_char(if text.starts_with('-') { w.wrapping_neg() } else { w }));
+            }
+            Machine::Dec => target.push_str(&word(1).to_string()),
+            Machine::Bar => {
+                let (w, a) = (word(2), word(3));
+                let result = match arg(1).chars().next() {
+                    Some('+') => w.wrapping_add(a),
+                    Some('-') => w.wrapping_sub(a),
+                    Some('x') => w.wrapping_mul(a),
+                    Some('/') if a != 0 => w.wrapping_div(a),
+                    Some('R') if a != 0 => w.wrapping_rem(a),
+                    _ => return self.fail(11, "Irremediable error".to_string()),
+                };
+                target.push(word_char(result));
+            }
+        }
+        Ok(())
+    }
+}
+
+// The name of a call for a monitor message, as GpmVm prints an item that is
+// still being collected
+fn item_name(items: &[String]) -> String {
+    if items.len() > 1 {
+        items[0].clone()
+    } else {
+        format!("{}...\t(Incomplete)", items[0])
+    }
+}
+
+fn word_char(w: Cell) -> char {
+    let code = if w >= 0 {
+        w as u32
+    } else {
+        match NEGATIVE_BASE.checked_add(w.unsigned_abs() - 1) {
+            Some(c) if c <= NEGATIVE_LAST => c,
+            _ => return '\u{FFFD}',
+        }
+    };
+    char::from_u32(code).unwrap_or('\u{FFFD}')
+}
+
+fn word_of(c: char) -> Cell {
+    match c as u32 {
+        code @ NEGATIVE_BASE..=NEGATIVE_LAST => -((code - NEGATIVE_BASE) as Cell) - 1,
+        code => code as Cell,
+    }
+}
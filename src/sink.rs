@@ -0,0 +1,98 @@
+// sink.rs — where GpmVm writes its output
+//
+// Without a sink, output collects in the VM and run hands it back as a
+// String. With one (GpmVm::set_output_sink), every character that reaches the
+// output (a Load with H = 0, an UNDIVERT) goes to the sink as it is produced
+// and run returns an empty string. Monitor reports go to the same place,
+// unless a diagnostic sink is set (GpmVm::set_diagnostic_sink).
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::any::Any;
+
+pub trait OutputSink: AsAny {
+    fn write_char(&mut self, c: char);
+
+    // Called when run or end returns.
+    fn flush(&mut self) {}
+}
+
+// Lets GpmVm hand a boxed sink back as its own type, without relying on
+// dyn OutputSink converting to dyn Any (trait upcasting needs Rust 1.86).
+// Every sink has it through the blanket impl.
+pub trait AsAny: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl OutputSink for String {
+    fn write_char(&mut self, c: char) {
+        self.push(c);
+    }
+}
+
+// A closure called with every character
+pub struct FnSink<F>(pub F);
+
+impl<F: FnMut(char) + 'static> OutputSink for FnSink<F> {
+    fn write_char(&mut self, c: char) {
+        (self.0)(c)
+    }
+}
+
+// Any io::Write, as UTF-8. Writes are per character, so give it a buffered
+// writer. After the first error nothing more is written; the error is kept
+// for the host (take the sink back from the VM to look at it).
+#[cfg(feature = "std")]
+pub struct IoSink<W> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoSink<W> {
+    pub fn new(writer: W) -> Self {
+        IoSink {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + 'static> OutputSink for IoSink<W> {
+    fn write_char(&mut self, c: char) {
+        if self.error.is_none() {
+            let mut buf = [0; 4];
+            if let Err(e) = self.writer.write_all(c.encode_utf8(&mut buf).as_bytes()) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.error.is_none() {
+            if let Err(e) = self.writer.flush() {
+                self.error = Some(e);
+            }
+        }
+    }
+}
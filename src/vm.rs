@@ -12,7 +12,18 @@
 // We read input as a stream of Rust `char` so the warning character '§' works correctly
 // even if the input is UTF-8. Store cells are i32, matching Appendix 2 "index" usage.
 
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::GpmError;
+use crate::include::{FileResolver, IncludeError};
+use crate::input::InputStack;
 use crate::pc::Pc;
+use crate::sink::OutputSink;
 use crate::{Cell, ControlChars};
 
 type Idx = i32;
@@ -20,11 +31,80 @@ type Idx = i32;
 // Appendix 2: Marker = -2**20 (Titan-style). We use the same sentinel.
 const MARKER: Cell = -(1 << 20);
 
+// First cell of a value whose parameter header DEF has resolved. The value is
+// then PARAMS, the number of parameters, the number of defaults, each default
+// as an item, and the body.
+const PARAMS: Cell = MARKER - 1;
+
+// Machine macros beyond Appendix 2. They are chained onto E after the MST and
+// marked the same way; the first one gets tag -7 (right after BAR = -6).
+const EXT_MACROS: &[(&str, Pc)] = &[
+    ("INCLUDE", Pc::INCLUDE),
+    ("DIVERT", Pc::DIVERT),
+    ("UNDIVERT", Pc::UNDIVERT),
+    ("UNDEF", Pc::UNDEF),
+    ("DEFINED", Pc::DEFINED),
+    ("ERROR", Pc::ERROR),
+    ("WARN", Pc::WARN),
+    ("UPPER", Pc::UPPER),
+    ("LOWER", Pc::LOWER),
+    ("CLASS", Pc::CLASS),
+    ("INDEX", Pc::INDEX),
+    ("REPLACE", Pc::REPLACE),
+    ("TRANSLIT", Pc::TRANSLIT),
+    ("VAR", Pc::VAR),
+    ("GENSYM", Pc::GENSYM),
+    ("CHANGECC", Pc::CHANGECC),
+    ("FOREACH", Pc::FOREACH),
+    ("GDEF", Pc::GDEF),
+];
+
+// Named-parameter header of a DEF value: a first line ~<name,...> written
+// with the LoadArg, quote and separator characters. The last
+// `defaults.len()` parameters may be left out of a call; `body` is the first
+// cell after the header line.
+struct Params {
+    names: Vec<Vec<Cell>>,
+    defaults: Vec<Vec<Cell>>,
+    body: Idx,
+}
+
+// The same header once it is stored (see PARAMS): names are gone, as the
+// body refers to positions only.
+struct Arity {
+    max: usize,
+    defaults: Vec<Vec<Cell>>,
+    body: Idx,
+}
+
 pub struct GpmVm {
     cc: ControlChars,
     mem_size: usize,
-    input: String,
+    input: InputStack,
+    // output when there is no sink (see sink.rs)
     output: String,
+    sink: Option<Box<dyn OutputSink>>,
+    diagnostic_sink: Option<Box<dyn OutputSink>>,
+
+    // INCLUDE support: paths go through the resolver, file text onto the input
+    resolver: Option<Box<dyn FileResolver>>,
+
+    // DIVERT support: where H=0 output currently goes (0 = output,
+    // negative = discarded) and the text collected so far per diversion
+    diversion: Cell,
+    diversions: BTreeMap<Cell, String>,
+
+    // Monitor reports so far; while `reporting` is set, everything written by
+    // write_symbol also goes into the last one. Warnings come from WARN.
+    errors: Vec<GpmError>,
+    reporting: bool,
+    warnings: Vec<String>,
+
+    // host variables read by VAR
+    vars: BTreeMap<String, String>,
+
+    // last number handed out by GENSYM
+    gensym: u64,
 
     // fixed store
     st: Vec<Cell>,
@@ -43,16 +123,47 @@ pub struct GpmVm {
     q: Idx,
 
     pc: Pc,
+    // state to carry on with when the next chunk arrives (see run)
+    resume: Pc,
+    // set_discard_newline
+    discard_newline: bool,
+    // digits of a ~{n} reference read so far
+    arg_ref: Vec<Cell>,
+    // a top-level call has just finished (discard_newline)
+    skip_newline: bool,
+    // debug builds: check_invariants after every step (set_step_checks)
+    step_checks: bool,
 }
 
 impl GpmVm {
+    // mem_size must be at least min_mem_size.
     pub fn new(control_chars: ControlChars, mem_size: usize) -> Self {
+        assert!(
+            mem_size >= Self::min_mem_size(),
+            "mem_size {} cannot hold the machine macros",
+            mem_size
+        );
         let mut vm = GpmVm {
             cc: control_chars,
             mem_size,
 
-            input: "".to_string(),
+            input: InputStack::new(),
             output: "".to_string(),
+            sink: None,
+            diagnostic_sink: None,
+
+            resolver: None,
+
+            diversion: 0,
+            diversions: BTreeMap::new(),
+
+            errors: Vec::new(),
+            reporting: false,
+            warnings: Vec::new(),
+
+            vars: BTreeMap::new(),
+
+            gensym: 0,
 
             st: vec![0; mem_size],
 
@@ -71,12 +182,42 @@ impl GpmVm {
             q: 1,
 
             pc: Pc::Start,
+            resume: Pc::Start,
+            discard_newline: false,
+            arg_ref: Vec::new(),
+            skip_newline: false,
+            step_checks: false,
         };
 
         vm.init_mst();
+        vm.init_ext_macros();
         vm
     }
 
+    // Store cells the machine macros take up: the MST of Appendix 2 and the
+    // entries after it. A smaller store is refused by new.
+    pub fn min_mem_size() -> usize {
+        39 + EXT_MACROS.iter().map(|(name, _)| name.chars().count() + 3).sum::<usize>()
+    }
+
+    // new, with the bundled prelude (Suc, Eq, Add, Sub, Mul, NewCounter,
+    // Next, Fact) already defined, written in the given control characters.
+    // The definitions prelude_for leaves out for the comment character are
+    // missing; a monitor raised while loading the rest (the store is too
+    // small) is returned instead.
+    pub fn with_prelude(control_chars: ControlChars, mem_size: usize) -> Result<Self, GpmError> {
+        let mut vm = Self::new(control_chars, mem_size);
+        let (prelude, _) = crate::prelude::prelude_for(control_chars);
+        vm.try_run(&prelude)?;
+        Ok(vm)
+    }
+
+    // Paths given to INCLUDE go through this resolver; without one every
+    // INCLUDE fails with Monitor14.
+    pub fn set_file_resolver(&mut self, resolver: impl FileResolver + 'static) {
+        self.resolver = Some(Box::new(resolver));
+    }
+
     // Appendix 2 uses "-2 ↑ 20" (Titan-style). For our faithful VM we just need
     // a stable negative sentinel value unlikely to collide with machine macro tags.
 
@@ -86,6 +227,218 @@ impl GpmVm {
         i as usize
     }
 
+    // Text diverted to buffer n so far (empty if nothing was diverted there).
+    // Diversions are not flushed by run/end; the host collects them here.
+    pub fn diversion(&self, n: Cell) -> &str {
+        self.diversions.get(&n).map_or("", |d| d.as_str())
+    }
+
+    pub fn take_diversion(&mut self, n: Cell) -> String {
+        self.diversions.remove(&n).unwrap_or_default()
+    }
+
+    // Output from now on goes to the sink as it is produced, and run and end
+    // return empty strings. Monitor reports too, unless there is a
+    // diagnostic sink.
+    pub fn set_output_sink(&mut self, sink: impl OutputSink) {
+        self.sink = Some(Box::new(sink));
+    }
+
+    // The output sink, if it is a T; output is collected by run again.
+    pub fn take_output_sink<T: OutputSink>(&mut self) -> Option<T> {
+        take_sink(&mut self.sink)
+    }
+
+    // Monitor reports from now on go here only (they are still recorded in
+    // errors), keeping them out of the output.
+    pub fn set_diagnostic_sink(&mut self, sink: impl OutputSink) {
+        self.diagnostic_sink = Some(Box::new(sink));
+    }
+
+    pub fn take_diagnostic_sink<T: OutputSink>(&mut self) -> Option<T> {
+        take_sink(&mut self.diagnostic_sink)
+    }
+
+    // Text read before the rest of the input, as INCLUDE reads a file;
+    // between runs, before the input of the next run. name shows in
+    // input_position.
+    pub fn push_input(&mut self, name: &str, text: &str) {
+        self.input.push(name, text);
+    }
+
+    // Name and line (from 1) of the source the next input character comes
+    // from: "input" for the text given to run, whose lines are counted
+    // across chunks, or the name of an included file or pushed text.
+    pub fn input_position(&self) -> (&str, usize) {
+        self.input.position()
+    }
+
+    // Every monitor raised since the last take_errors, oldest first.
+    // The report text is also written out: to the diagnostic sink if one is
+    // set, otherwise to the output.
+    // Nothing else clears the list, so a host that keeps a VM running should
+    // call take_errors after each run (try_run does not).
+    pub fn errors(&self) -> &[GpmError] {
+        &self.errors
+    }
+
+    pub fn take_errors(&mut self) -> Vec<GpmError> {
+        core::mem::take(&mut self.errors)
+    }
+
+    // Messages recorded by WARN since the last take_warnings (kept until
+    // then, like errors).
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.warnings)
+    }
+
+    // VAR,name; loads the value straight into the output or argument, so
+    // control characters in it are never scanned (unless the text is later
+    // made into a macro body, as with any other argument).
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove_var(&mut self, name: &str) -> Option<String> {
+        self.vars.remove(name)
+    }
+
+    // GENSYM counter; hosts that persist a VM's state carry it with the
+    // definitions so that symbols stay unique across sessions.
+    pub fn gensym_counter(&self) -> u64 {
+        self.gensym
+    }
+
+    pub fn set_gensym_counter(&mut self, n: u64) {
+        self.gensym = n;
+    }
+
+    // Drop a newline that comes straight after a top-level call (cf. m4
+    // dnl), so a file of definitions can have one per line. Off by default.
+    pub fn set_discard_newline(&mut self, on: bool) {
+        self.discard_newline = on;
+    }
+
+    // Control characters in force now (CHANGECC may have altered them).
+    pub fn control_chars(&self) -> ControlChars {
+        self.cc
+    }
+
+    // In debug builds, run check_invariants after every step and panic at the
+    // first transition that breaks the store (a monitor report counts as one
+    // step). Ignored in release builds.
+    pub fn set_step_checks(&mut self, on: bool) {
+        self.step_checks = on;
+    }
+
+    // Structural checks on the store and registers: the P and F chains and
+    // the E links descend, every complete item lies below S, and q >= 1.
+    // Returns a description of the first violation found; a corrupt link or
+    // length is reported, never followed out of the store.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        // in i64, so that a corrupt length cannot overflow the sums below
+        let s = self.s as i64;
+        if s < 0 || s > self.mem_size as i64 {
+            return Err(format!("S = {} outside the store", s));
+        }
+        if self.q < 1 {
+            return Err(format!("q = {}", self.q));
+        }
+        let h = self.h as i64;
+        if h < 0 || h >= s.max(1) {
+            return Err(format!("H = {} not below S = {}", h, s));
+        }
+        let cell = |x: i64| {
+            if x < 0 || x >= s {
+                return Err(format!("cell {} read above S = {}", x, s));
+            }
+            Ok(self.st[x as usize] as i64)
+        };
+
+        // entered calls: ST[P-1] frame length, ST[P] saved P, ST[P+1] saved C,
+        // then complete items up to the Marker
+        let mut frames = Vec::new();
+        let mut p = self.p as i64;
+        while p != 0 {
+            if p < 1 || p + 2 >= s {
+                return Err(format!("P chain: frame at {} outside 1..S", p));
+            }
+            if p - 1 + cell(p - 1)? > s {
+                return Err(format!("frame at P = {} runs past S", p));
+            }
+            let mut x = p + 2;
+            while cell(x)? != MARKER as i64 {
+                let len = cell(x)?;
+                if len < 1 || x + len >= s {
+                    return Err(format!("item at {} in frame at P = {} has length {}", x, p, len));
+                }
+                x += len;
+            }
+            if cell(p)? >= p {
+                return Err(format!("P chain: {} links up to {}", p, cell(p)?));
+            }
+            frames.push((p - 1, cell(p - 1)?));
+            p = cell(p)?;
+        }
+
+        // calls not yet entered: ST[F-1] saved H, ST[F] saved F, then complete
+        // items up to the one being collected (at H for the newest call)
+        let mut f = self.f as i64;
+        let mut current = h;
+        while f != 0 {
+            if f < 1 || f + 2 >= s {
+                return Err(format!("F chain: frame at {} outside 1..S", f));
+            }
+            let mut x = f + 2;
+            while x < current {
+                let len = cell(x)?;
+                if len < 1 {
+                    return Err(format!("item at {} in frame at F = {} has length {}", x, f, len));
+                }
+                // an item finished inside the body of a call begun in it
+                // still holds that call's frame, which its length leaves out
+                let mut end = x + len;
+                for &(start, size) in frames.iter().rev() {
+                    if x < start && start <= end {
+                        end += size;
+                    }
+                }
+                x = end;
+            }
+            if x != current {
+                return Err(format!("items of frame at F = {} overrun H = {}", f, current));
+            }
+            if cell(f)? >= f {
+                return Err(format!("F chain: {} links up to {}", f, cell(f)?));
+            }
+            current = cell(f - 1)?;
+            f = cell(f)?;
+        }
+
+        // definitions: links strictly decrease to -1, name and value items
+        // (or a machine macro tag) below S
+        let mut e = self.e as i64;
+        while e != -1 {
+            if e < 0 || e + 1 >= s {
+                return Err(format!("E chain: entry at {} outside 0..S", e));
+            }
+            let len = cell(e + 1)?;
+            if len < 1 || e + 1 + len >= s {
+                return Err(format!("name item of entry at {} has length {}", e, len));
+            }
+            let w = e + 1 + len;
+            let value = cell(w)?;
+            if value >= 0 && (value < 1 || w + value > s) {
+                return Err(format!("value item of entry at {} has length {}", e, value));
+            }
+            if cell(e)? >= e {
+                return Err(format!("E chain: {} links up to {}", e, cell(e)?));
+            }
+            e = cell(e)?;
+        }
+        Ok(())
+    }
+
     fn init_mst(&mut self) {
         // MST from Appendix 2 (39 cells, copied to base of ST)
         // Name-value pairs for machine macros: DEF, VAL, UPDATE, BIN, DEC, BAR
@@ -147,25 +500,72 @@ impl GpmVm {
         self.q = 1;
     }
 
+    fn init_ext_macros(&mut self) {
+        // Same layout as the MST entries: link, len, name chars..., negative tag
+        for (k, (name, _)) in EXT_MACROS.iter().enumerate() {
+            let at = self.s;
+            let len = name.chars().count() as Idx + 1;
+
+            self.st[Self::u(at)] = self.e as Cell;
+            self.st[Self::u(at + 1)] = len as Cell;
+            for (r, ch) in name.chars().enumerate() {
+                self.st[Self::u(at + 2) + r] = ch as Cell;
+            }
+            self.st[Self::u(at + 1 + len)] = -(7 + k as Cell);
+
+            self.e = at;
+            self.s = at + len + 2;
+        }
+    }
+
     // WriteSymbol[A]
     fn write_symbol(&mut self, x: Cell) {
-        let ch = char::from_u32(x as u32).unwrap_or('\u{FFFD}');
-        self.output.push(ch);
+        let ch = Self::to_char(x);
+        if self.reporting {
+            if let Some(e) = self.errors.last_mut() {
+                e.report.push(ch);
+            }
+            if let Some(sink) = &mut self.diagnostic_sink {
+                sink.write_char(ch);
+                return;
+            }
+        }
+        match &mut self.sink {
+            Some(sink) => sink.write_char(ch),
+            None => self.output.push(ch),
+        }
+    }
+
+    fn flush_sinks(&mut self) {
+        for sink in [&mut self.sink, &mut self.diagnostic_sink].into_iter().flatten() {
+            sink.flush();
+        }
+    }
+
+    // Load with H = 0 ends up here; monitors write with write_symbol directly,
+    // so diagnostics are never diverted.
+    fn emit(&mut self, x: Cell) {
+        match self.diversion {
+            0 => self.write_symbol(x),
+            n if n < 0 => {}
+            n => self.diversions.entry(n).or_default().push(Self::to_char(x)),
+        }
+    }
+
+    #[inline]
+    fn to_char(x: Cell) -> char {
+        char::from_u32(x as u32).unwrap_or('\u{FFFD}')
     }
 
     // ReadSymbol[A]
     fn read_symbol(&mut self) -> Option<Cell> {
-        let x = self.input.pop();
-        match x {
-            Some(x) => { /* eprint!("{}", x); */ Some(x as Cell) }
-            None => None,
-        }
+        self.input.next().map(|x| x as Cell)
     }
 
     // routine Load
     fn load(&mut self) {
         if self.h == 0 {
-            self.write_symbol(self.a);
+            self.emit(self.a);
         } else {
             let s = Self::u(self.s);
             if s >= self.mem_size {
@@ -203,6 +603,12 @@ impl GpmVm {
         if !self.next_ch() {
             return Pc::NoInput;
         }
+        if self.skip_newline {
+            self.skip_newline = false;
+            if self.a == '\n' as Cell {
+                return Pc::Start;
+            }
+        }
         match self.a {
             x if x == self.cc.open => {
                 self.q += 1;
@@ -223,13 +629,9 @@ impl GpmVm {
                     Pc::Apply
                 }
             }
-            x if x == self.cc.load_arg => {
-                if self.p == 0 {
-                    Pc::Copy
-                } else {
-                    Pc::LoadArg
-                }
-            }
+            // LoadArg itself copies a ~ read outside any macro body, or
+            // raises Monitor2 for one in an argument list
+            x if x == self.cc.load_arg => Pc::LoadArg,
             MARKER => {
                 if self.h == 0 && self.c == 0 {
                     Pc::Finish
@@ -244,10 +646,29 @@ impl GpmVm {
                     Pc::Exit
                 }
             }
+            x if Some(x) == self.cc.comment => Pc::Comment,
             _ => Pc::Copy,
         }
     }
 
+    // Comment: NextCh until a newline, which is dropped as well.
+    // A comment in a macro body also ends at the body's Marker, which is then
+    // read again by Start.
+    fn op_comment(&mut self) -> Pc {
+        loop {
+            if !self.next_ch() {
+                return Pc::NoInput;
+            }
+            if self.a == '\n' as Cell {
+                return Pc::Start;
+            }
+            if self.a == MARKER {
+                self.c -= 1;
+                return Pc::Start;
+            }
+        }
+    }
+
     // Copy: Load
     fn op_copy(&mut self) -> Pc {
         self.load();
@@ -308,6 +729,7 @@ impl GpmVm {
         loop {
             // bounds (minimal sanity)
             if a < 0 || w < 0 {
+                self.w = x as Cell;
                 self.pc = Pc::Monitor(7);
                 return;
             }
@@ -359,6 +781,7 @@ impl GpmVm {
 
             // repeat until A < 0
             if a < 0 {
+                self.w = x as Cell; // W is still x at Monitor7
                 self.pc = Pc::Monitor(7);
                 return;
             }
@@ -378,7 +801,8 @@ impl GpmVm {
             return None;
         }
 
-        // x is a negative tag: -1..-6 (per MST)
+        // x is a negative tag: -1..-6 (per MST), -7.. (EXT_MACROS)
+        #[allow(clippy::unnecessary_cast)]
         let idx = (-x) as i32;
         match idx {
             1 => Some(Pc::DEF),
@@ -387,6 +811,9 @@ impl GpmVm {
             4 => Some(Pc::BIN),
             5 => Some(Pc::DEC),
             6 => Some(Pc::BAR),
+            n if n >= 7 && ((n - 7) as usize) < EXT_MACROS.len() => {
+                Some(EXT_MACROS[(n - 7) as usize].1)
+            }
             _ => {
                 // Unknown negative tag: treat as fatal internal error for now
                 self.pc = Pc::Monitor(11);
@@ -470,6 +897,7 @@ impl GpmVm {
         Pc::Start
     }
 
+    #[allow(clippy::assign_op_pattern)]
     fn op_apply(&mut self) -> Pc {
         // Appendix 2 (Warning Character Actions):
         // Apply:  if P > F goto Monitor1
@@ -540,6 +968,9 @@ impl GpmVm {
 
         // Find[P+2]
         self.find(self.p + 2);
+        if matches!(self.pc, Pc::Monitor(_)) {
+            return self.pc;
+        }
 
         // JumpIfMarked[ST[W]]
         let tag = self.st[Self::u(self.w as Idx)];
@@ -548,8 +979,15 @@ impl GpmVm {
         }
 
         // C := W+1 ; goto Start
-        self.c = (self.w as Idx) + 1;
-        Pc::Start
+        // (past the parameter header, if the value has one, once the call
+        // has been checked against it)
+        match self.arity(self.w as Idx) {
+            Some(arity) => self.apply_params(&arity),
+            None => {
+                self.c = (self.w as Idx) + 1;
+                Pc::Start
+            }
+        }
     }
 
     fn op_load_arg(&mut self) -> Pc {
@@ -567,6 +1005,9 @@ impl GpmVm {
         //              Load
         //            }
         //          goto Start
+        //
+        // Extensions: ~{n} for any number of digits (see op_arg_ref) and ~#
+        // for the number of arguments; both walk the items from P+2 like ~n.
 
         if self.p == 0 {
             // goto H=0 -> Copy, Monitor2
@@ -581,14 +1022,71 @@ impl GpmVm {
             return Pc::NoInput;
         }
 
-        // W := P+2
-        let mut w: Idx = self.p + 2;
+        if self.a == '{' as Cell {
+            self.arg_ref.clear();
+            return Pc::ArgRef;
+        }
+        if self.a == '#' as Cell {
+            return self.op_arg_count();
+        }
 
         // if Number[A] < 0 goto Monitor3
         let x = Self::number(self.a);
         if x < 0 {
             return Pc::Monitor(3);
         }
+        self.load_arg_number(x)
+    }
+
+    // ~{digits}: NextCh up to the closing brace, then as LoadArg.
+    // The digits are kept in arg_ref, so a reference split between two chunks
+    // carries on when the next one arrives.
+    fn op_arg_ref(&mut self) -> Pc {
+        loop {
+            if !self.next_ch() {
+                return Pc::NoInput;
+            }
+            if self.a == '}' as Cell {
+                break;
+            }
+            if self.a == MARKER {
+                self.arg_ref.clear();
+                return Pc::Monitor(3);
+            }
+            self.arg_ref.push(self.a);
+        }
+
+        let digits: String = self.arg_ref.iter().map(|&x| Self::to_char(x)).collect();
+        let x = match digits.parse::<Idx>() {
+            Ok(x) if digits.chars().all(|c| c.is_ascii_digit()) => x,
+            _ => {
+                self.arg_ref.clear();
+                return Pc::Monitor(3);
+            }
+        };
+        let next = self.load_arg_number(x);
+        if next != Pc::Monitor(4) {
+            self.arg_ref.clear();
+        }
+        next
+    }
+
+    // ~#: the number of arguments, not counting the macro name (~0)
+    fn op_arg_count(&mut self) -> Pc {
+        let Some(n) = self.arg_count() else {
+            return Pc::Monitor(11);
+        };
+        if self.load_text(&n.to_string()) == Pc::Monitor(11) {
+            return Pc::Monitor(11);
+        }
+        Pc::Start
+    }
+
+    // The rest of LoadArg, from W := P+2, for argument number x
+    #[allow(clippy::assign_op_pattern)]
+    fn load_arg_number(&mut self, x: Idx) -> Pc {
+        // W := P+2
+        let mut w: Idx = self.p + 2;
 
         // for f = 0 to Number[A]-1 do ...
         for _ in 0..x {
@@ -759,6 +1257,11 @@ impl GpmVm {
         self.c = new_c;
         self.s = new_s;
 
+        // back in the input stream at top level: a newline may follow
+        if self.discard_newline && self.p == 0 && self.h == 0 && self.c == 0 {
+            self.skip_newline = true;
+        }
+
         // until A=S do ST[A], A, W := ST[W], A+1, W+1
         while a2 != self.s {
             let a2_u = Self::u(a2);
@@ -808,6 +1311,10 @@ impl GpmVm {
             return Pc::Monitor(11);
         }
 
+        if let Pc::Monitor(n) = self.resolve_value_params() {
+            return Pc::Monitor(n);
+        }
+
         // unless H = 0 do ST[H] := ST[H] - ST[P-1] + 6
         if self.h != 0 {
             let h = self.h;
@@ -839,7 +1346,11 @@ impl GpmVm {
             return self.pc;
         }
 
-        let mut w: Idx = self.w as Idx;
+        // a parameter header is not part of the text
+        let mut w: Idx = match self.arity(self.w as Idx) {
+            Some(arity) => arity.body - 1,
+            None => self.w as Idx,
+        };
         loop {
             let wp1 = w + 1;
             if wp1 < 0 || Self::u(wp1) >= self.mem_size {
@@ -891,12 +1402,17 @@ impl GpmVm {
         }
         let len_old: Idx = self.st[Self::u(w)] as Idx;
 
-        if len_new > len_old {
+        // a parameter header stays; the new text goes where the body begins
+        let base: Idx = match self.arity(w) {
+            Some(arity) => arity.body - 1,
+            None => w,
+        };
+        if len_new > w + len_old - base {
             return Pc::Monitor(9);
         }
 
         for r in 1..=len_new {
-            let dst = w + r;
+            let dst = base + r;
             let src = a0 + r;
             if dst < 0
                 || src < 0
@@ -945,21 +1461,23 @@ impl GpmVm {
                 return Pc::Monitor(10);
             }
 
-            w_acc = 10 * w_acc + x;
+            // a number too long for a cell wraps round, as in ReferenceGpm
+            w_acc = w_acc.wrapping_mul(10).wrapping_add(x);
             a += 1;
         }
 
-        self.s += 1;
+        // S, ST[S] := S+1, W: the number goes in the cell at the old S
         let su = Self::u(self.s);
         if su >= self.mem_size {
             return Pc::Monitor(11);
         }
 
         self.st[su] = if sign_ch == ('-' as Cell) {
-            -(w_acc as Cell)
+            (w_acc as Cell).wrapping_neg()
         } else {
             w_acc as Cell
         };
+        self.s += 1;
 
         Pc::EndFn
     }
@@ -975,7 +1493,8 @@ impl GpmVm {
             return Pc::Monitor(11);
         }
 
-        let mut w: Idx = self.st[Self::u(p7)] as Idx;
+        // in i64, so that the most negative cell has a magnitude too
+        let mut w = i64::from(self.st[Self::u(p7)]);
 
         if w < 0 {
             w = -w;
@@ -987,7 +1506,7 @@ impl GpmVm {
         }
 
         // W1 := 1; until 10*W1 > W do W1 := 10*W1
-        let mut w1: Idx = 1;
+        let mut w1: i64 = 1;
         while 10 * w1 <= w {
             w1 *= 10;
         }
@@ -998,7 +1517,7 @@ impl GpmVm {
             let r = if w1 != 0 { w % w1 } else { w };
 
             // Char[q]
-            self.a = ('0' as Idx + q) as Cell;
+            self.a = ('0' as i64 + q) as Cell;
             self.load();
             if matches!(self.pc, Pc::Monitor(_)) {
                 return self.pc;
@@ -1031,20 +1550,21 @@ impl GpmVm {
         let av: Idx = self.st[Self::u(p11)] as Idx;
 
         let res: Idx = match op {
-            x if x == ('+' as Cell) => wv + av,
-            x if x == ('-' as Cell) => wv - av,
-            x if x == ('x' as Cell) => wv * av,
+            // overflow wraps round, as in ReferenceGpm
+            x if x == ('+' as Cell) => wv.wrapping_add(av),
+            x if x == ('-' as Cell) => wv.wrapping_sub(av),
+            x if x == ('x' as Cell) => wv.wrapping_mul(av),
             x if x == ('/' as Cell) => {
                 if av == 0 {
                     return Pc::Monitor(11);
                 } // (tu brak monitora w Appendix; traktuję jako błąd wewn.)
-                wv / av
+                wv.wrapping_div(av)
             }
             x if x == ('R' as Cell) => {
                 if av == 0 {
                     return Pc::Monitor(11);
                 }
-                wv % av
+                wv.wrapping_rem(av)
             }
             _ => return Pc::Monitor(11),
         };
@@ -1058,98 +1578,1081 @@ impl GpmVm {
         Pc::EndFn
     }
 
-    fn step(&mut self) {
-        let next = match self.pc {
-            // main cycle
-            Pc::Start => self.op_start(),
-            Pc::Copy => self.op_copy(),
-            Pc::Scan => self.op_scan(),
-            Pc::Q2 => self.op_q2(),
-
-            // warning char actions
-            Pc::Fn => self.op_fn(),
-            Pc::NextItem => self.op_next_item(),
-            Pc::Apply => self.op_apply(),
-            Pc::LoadArg => self.op_load_arg(),
-            Pc::EndFn => self.op_end_fn(),
-            Pc::Exit => self.op_exit(),
-
-            // machine code macros
-            Pc::DEF => self.op_def(),
-            Pc::VAL => self.op_val(),
-            Pc::UPDATE => self.op_update(),
-            Pc::BIN => self.op_bin(),
-            Pc::DEC => self.op_dec(),
-            Pc::BAR => self.op_bar(),
-
-            Pc::Monitor(n) => self.monitor(n),
-            Pc::Finish => panic!("Finish"),
-            Pc::NoInput => panic!("NoInput"),
-        };
+    // ===== Helpers for the extension machine macros =====
 
-        self.pc = next;
-    }
-
-    // CPL Write['...'] with *n/*t/*s escapes
-    fn write_text(&mut self, s: &str) {
-        let mut it = s.chars().peekable();
-        while let Some(ch) = it.next() {
-            if ch == '*' {
-                match it.peek().copied() {
-                    Some('n') => {
-                        it.next();
-                        self.write_symbol('\n' as Cell);
-                    }
-                    Some('t') => {
-                        it.next();
-                        self.write_symbol('\t' as Cell);
-                    }
-                    Some('s') => {
-                        it.next();
-                        self.write_symbol(' ' as Cell);
-                    }
-                    _ => self.write_symbol('*' as Cell),
-                }
-            } else {
-                self.write_symbol(ch as u32 as Cell);
+    // Item n of the current call (0 = macro name), found by walking from P+2
+    // the same way LoadArg does. A missing argument yields the Marker cell,
+    // which callers read as an empty item; None means the store is corrupt.
+    fn arg_item(&self, n: Idx) -> Option<Idx> {
+        let mut w: Idx = self.p + 2;
+        for _ in 0..n {
+            if w < 0 || Self::u(w) >= self.mem_size {
+                return None;
             }
+            if self.st[Self::u(w)] == MARKER {
+                return Some(w);
+            }
+            w += self.st[Self::u(w)] as Idx;
+        }
+        if w < 0 || Self::u(w) >= self.mem_size {
+            return None;
         }
+        Some(w)
     }
 
-    // Appendix 2 §3 Item[x]
-    fn item(&mut self, x: Idx) {
-        // save A,H
-        let a0 = self.a;
-        let h0 = self.h;
-        self.h = 0;
-
-        if x < 0 || Self::u(x) >= self.mem_size {
-            self.write_text("*n(Item: bad pointer)");
-            self.a = a0;
-            self.h = h0;
-            return;
+    // Characters of the complete item at x (empty for the Marker)
+    fn item_text(&self, x: Idx) -> Option<String> {
+        let len = self.st[Self::u(x)] as Idx;
+        if len == MARKER {
+            return Some(String::new());
+        }
+        if len < 1 || Self::u(x + len) > self.mem_size {
+            return None;
         }
+        Some((1..len).map(|r| Self::to_char(self.st[Self::u(x + r)])).collect())
+    }
 
-        let stx = self.st[Self::u(x)] as Idx;
+    // Cells of argument n (empty if missing)
+    fn arg_cells(&self, n: Idx) -> Option<Vec<Cell>> {
+        let x = self.arg_item(n)?;
+        let len = self.st[Self::u(x)] as Idx;
+        if len == MARKER as Idx {
+            return Some(Vec::new());
+        }
+        if len < 1 || Self::u(x + len) > self.mem_size {
+            return None;
+        }
+        Some(self.st[Self::u(x + 1)..Self::u(x + len)].to_vec())
+    }
 
-        // if ST[x]=0 → incomplete object
-        let end_k: Idx = if stx == 0 {
-            (self.s - x - 1).max(0)
-        } else {
-            (stx - 1).max(0)
+    // Header line of the value item at v: None if there is none, Some(None) if
+    // the first line starts like one but is not a list of distinct names, each
+    // optionally followed by =default (defaults only at the end of the list).
+    fn parse_params(&self, v: Idx) -> Option<Option<Params>> {
+        let end = v + self.st[Self::u(v)] as Idx;
+        let at = |k: Idx| {
+            if k < end && Self::u(k) < self.mem_size {
+                self.st[Self::u(k)]
+            } else {
+                MARKER
+            }
         };
+        if at(v + 1) != self.cc.load_arg || at(v + 2) != self.cc.open {
+            return None;
+        }
 
-        for k in 1..=end_k {
-            let idx = x + k;
-            if idx < 0 || Self::u(idx) >= self.mem_size {
-                break;
+        let mut entries = vec![Vec::new()];
+        let mut k = v + 3;
+        loop {
+            match at(k) {
+                MARKER => return Some(None),
+                x if x == self.cc.close => break,
+                x if x == self.cc.arg_sep => entries.push(Vec::new()),
+                x => entries.last_mut()?.push(x),
             }
-            self.a = self.st[Self::u(idx)];
-            self.load(); // with H=0 → output
+            k += 1;
         }
-
-        if stx == 0 {
-            self.write_text("...*t(Incomplete)");
+        k += 1;
+        match at(k) {
+            MARKER => {}
+            x if x == '\n' as Cell => k += 1,
+            _ => return Some(None),
+        }
+
+        let blank = |x: &Cell| *x == ' ' as Cell || *x == '\t' as Cell;
+        let trim = |mut cells: Vec<Cell>| {
+            while cells.last().is_some_and(blank) {
+                cells.pop();
+            }
+            let lead = cells.iter().take_while(|x| blank(x)).count();
+            cells.drain(..lead);
+            cells
+        };
+        if entries.len() == 1 && trim(entries[0].clone()).is_empty() {
+            entries.clear();
+        }
+        let is_name = |name: &Vec<Cell>| {
+            let text: String = name.iter().map(|&x| Self::to_char(x)).collect();
+            text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+        };
+
+        let mut names: Vec<Vec<Cell>> = Vec::new();
+        let mut defaults = Vec::new();
+        for mut entry in entries {
+            let default = match entry.iter().position(|&x| x == '=' as Cell) {
+                Some(eq) => {
+                    let value = entry.split_off(eq + 1);
+                    entry.pop();
+                    Some(trim(value))
+                }
+                None if !defaults.is_empty() => return Some(None),
+                None => None,
+            };
+            let name = trim(entry);
+            if !is_name(&name) || names.contains(&name) {
+                return Some(None);
+            }
+            names.push(name);
+            defaults.extend(default);
+        }
+        Some(Some(Params {
+            names,
+            defaults,
+            body: k,
+        }))
+    }
+
+    // Stored parameter header of the value item at v, if it has one
+    fn arity(&self, v: Idx) -> Option<Arity> {
+        if v < 0 || Self::u(v) >= self.mem_size {
+            return None;
+        }
+        let end = v + self.st[Self::u(v)] as Idx;
+        if end <= v || Self::u(end) > self.mem_size {
+            return None;
+        }
+        let at = |k: Idx| if k < end { Some(self.st[Self::u(k)]) } else { None };
+        if at(v + 1)? != PARAMS {
+            return None;
+        }
+        let max = usize::try_from(at(v + 2)?).ok()?;
+        let count = usize::try_from(at(v + 3)?).ok().filter(|&d| d <= max)?;
+
+        let mut defaults = Vec::with_capacity(count);
+        let mut k = v + 4;
+        for _ in 0..count {
+            let len = at(k)? as Idx;
+            if len < 1 || k + len > end {
+                return None;
+            }
+            defaults.push(self.st[Self::u(k + 1)..Self::u(k + len)].to_vec());
+            k += len;
+        }
+        Some(Arity {
+            max,
+            defaults,
+            body: k,
+        })
+    }
+
+    // Number of arguments in the current call
+    fn arg_count(&self) -> Option<Idx> {
+        let mut n: Idx = 0;
+        loop {
+            let w = self.arg_item(n + 1)?;
+            if self.st[Self::u(w)] == MARKER {
+                return Some(n);
+            }
+            n += 1;
+        }
+    }
+
+    // Rest of Apply for a macro with a parameter header: the number of
+    // arguments must fit the header, and missing trailing arguments are
+    // appended to the frame from their defaults before the body starts.
+    fn apply_params(&mut self, params: &Arity) -> Pc {
+        let Some(n) = self.arg_count() else {
+            return Pc::Monitor(11);
+        };
+        let max = params.max;
+        let min = max - params.defaults.len();
+        let n = n as usize;
+        if n < min || n > max {
+            return Pc::Monitor(18);
+        }
+
+        // the frame ends with its Marker at S-1
+        let s0 = self.s;
+        let mut k = s0 - 1;
+        for default in &params.defaults[n - min..] {
+            let len = default.len() as Idx + 1;
+            if Self::u(k + len) >= self.mem_size {
+                return Pc::Monitor(11);
+            }
+            self.st[Self::u(k)] = len as Cell;
+            let start = Self::u(k + 1);
+            self.st[start..start + default.len()].copy_from_slice(default);
+            k += len;
+        }
+        self.st[Self::u(k)] = MARKER;
+        self.s = k + 1;
+
+        let d = (self.s - s0) as Cell;
+        self.st[Self::u(self.p - 1)] += d;
+        if self.h != 0 {
+            self.st[Self::u(self.h)] += d;
+        }
+
+        self.c = params.body;
+        Pc::Start
+    }
+
+    // Named parameters of the value being defined (argument 2 of DEF or
+    // GDEF): ~{name} in the body becomes ~{position} now, so calls never look
+    // names up, and the header line is replaced by its stored form.
+    fn resolve_value_params(&mut self) -> Pc {
+        let (Some(x), Some(v)) = (self.arg_item(1), self.arg_item(2)) else {
+            return Pc::Monitor(11);
+        };
+        match self.parse_params(v) {
+            None => Pc::EndFn,
+            Some(None) => {
+                self.w = x as Cell;
+                Pc::Monitor(17)
+            }
+            Some(Some(params)) if !self.resolve_params(v, &params) => Pc::Monitor(11),
+            Some(Some(_)) => Pc::EndFn,
+        }
+    }
+
+    // Rewrites ~{name} outside nested quotes in the body of the value item at
+    // v to ~{position}, and the header line to PARAMS and what follows it,
+    // moving the rest of the frame (later items and the Marker) to follow
+    // the new value. Unknown names are left alone and fail at call time like
+    // any other impossible argument.
+    fn resolve_params(&mut self, v: Idx, params: &Params) -> bool {
+        let end = v + self.st[Self::u(v)] as Idx;
+        if Self::u(end) > self.mem_size || end > self.s {
+            return false;
+        }
+        let cells = self.st[Self::u(v + 1)..Self::u(end)].to_vec();
+        let body = Self::u(params.body - v - 1);
+        let mut out = vec![
+            PARAMS,
+            params.names.len() as Cell,
+            params.defaults.len() as Cell,
+        ];
+        for default in &params.defaults {
+            out.push(default.len() as Cell + 1);
+            out.extend(default);
+        }
+
+        let mut depth = 0;
+        let mut k = body;
+        while k < cells.len() {
+            let x = cells[k];
+            if x == self.cc.open {
+                depth += 1;
+            } else if x == self.cc.close && depth > 0 {
+                depth -= 1;
+            } else if depth == 0
+                && x == self.cc.load_arg
+                && cells.get(k + 1) == Some(&('{' as Cell))
+            {
+                let close = cells[k + 2..].iter().position(|&c| c == '}' as Cell);
+                let pos = close.and_then(|j| {
+                    let name = &cells[k + 2..k + 2 + j];
+                    let i = params.names.iter().position(|n| n.as_slice() == name)?;
+                    Some((j, i + 1))
+                });
+                if let Some((j, pos)) = pos {
+                    out.extend([x, '{' as Cell]);
+                    out.extend(pos.to_string().chars().map(|c| c as Cell));
+                    out.push('}' as Cell);
+                    k += j + 3;
+                    continue;
+                }
+            }
+            out.push(x);
+            k += 1;
+        }
+
+        let tail = self.st[Self::u(end)..Self::u(self.s)].to_vec();
+        let new_s = v + 1 + (out.len() + tail.len()) as Idx;
+        if Self::u(new_s) > self.mem_size {
+            return false;
+        }
+        self.st[Self::u(v)] = out.len() as Cell + 1;
+        let start = Self::u(v + 1);
+        self.st[start..start + out.len()].copy_from_slice(&out);
+        let start = start + out.len();
+        self.st[start..start + tail.len()].copy_from_slice(&tail);
+        self.s = new_s;
+        true
+    }
+
+    fn arg_text(&self, n: Idx) -> Option<String> {
+        self.item_text(self.arg_item(n)?)
+    }
+
+    // Optionally signed decimal, as accepted by BIN. Anything else is
+    // Monitor10, a number that does not fit in a cell Monitor19.
+    fn parse_number(text: &str) -> Result<Idx, Pc> {
+        let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Pc::Monitor(10));
+        }
+        text.parse().map_err(|_| Pc::Monitor(19))
+    }
+
+    // Loads the characters of the complete item at x, like LoadArg
+    fn load_item(&mut self, x: Idx) -> Pc {
+        let len = self.st[Self::u(x)] as Idx;
+        for r in 1..len {
+            self.a = self.st[Self::u(x + r)];
+            self.load();
+            if self.pc == Pc::Monitor(11) {
+                return Pc::Monitor(11);
+            }
+        }
+        Pc::EndFn
+    }
+
+    fn load_cells(&mut self, cells: &[Cell]) -> Pc {
+        for &cell in cells {
+            self.a = cell;
+            self.load();
+            if self.pc == Pc::Monitor(11) {
+                return Pc::Monitor(11);
+            }
+        }
+        Pc::EndFn
+    }
+
+    fn load_text(&mut self, text: &str) -> Pc {
+        let cells: Vec<Cell> = text.chars().map(|ch| ch as Cell).collect();
+        self.load_cells(&cells)
+    }
+
+    // Does the entry at a carry the name held in the item at x? (cf. Find)
+    fn entry_matches(&self, a: Idx, x: Idx) -> bool {
+        let len = self.st[Self::u(x)] as Idx;
+        if len < 1 || Self::u(a + len) >= self.mem_size || Self::u(x + len) > self.mem_size {
+            return false;
+        }
+        (0..len).all(|r| self.st[Self::u(x + r)] == self.st[Self::u(a + r + 1)])
+    }
+
+    // Find without Monitor7: (A, W) of the newest entry named by the item at x
+    fn lookup(&self, x: Idx) -> Option<(Idx, Idx)> {
+        let mut a = self.e;
+        while a >= 0 && Self::u(a) < self.mem_size {
+            if self.entry_matches(a, x) {
+                return Some((a, a + 1 + self.st[Self::u(x)] as Idx));
+            }
+            a = self.st[Self::u(a)] as Idx;
+        }
+        None
+    }
+
+    // One past the Marker closing the entry at a (link, name, value, ...)
+    fn entry_end(&self, a: Idx) -> Option<Idx> {
+        let mut w = a + 1;
+        loop {
+            if w < 0 || Self::u(w) >= self.mem_size {
+                return None;
+            }
+            match self.st[Self::u(w)] {
+                MARKER => return Some(w + 1),
+                len if len > 0 => w += len as Idx,
+                _ => return None,
+            }
+        }
+    }
+
+    // Does the cell at a survive every active call? Cells inside the argument
+    // list of an entered call (P chain), or anywhere above a call that is still
+    // collecting arguments (F chain), go when that call is finished; anything
+    // else, including DEFs in a body read with H = 0, stays.
+    fn is_top_level(&self, a: Idx) -> bool {
+        let mut f = self.f;
+        while f > 0 {
+            if a >= f - 1 {
+                return false;
+            }
+            f = self.st[Self::u(f)] as Idx;
+        }
+        let mut p = self.p;
+        while p > 0 {
+            if a >= p - 1 && a < p - 1 + self.st[Self::u(p - 1)] as Idx {
+                return false;
+            }
+            p = self.st[Self::u(p)] as Idx;
+        }
+        true
+    }
+
+    // Moves ST[from..S) by delta cells and corrects every absolute pointer
+    // into that range: the registers, saved P and C of entered calls, saved H
+    // and F of calls not yet entered, and the E links. Item lengths are
+    // relative and stay as they are.
+    fn relocate(&mut self, from: Idx, delta: Idx) -> bool {
+        let top = self.s + delta;
+        if from <= 0 || from + delta <= 0 || from > self.s || Self::u(top) > self.mem_size {
+            return false;
+        }
+        let moved = |x: Idx| if x >= from { x + delta } else { x };
+
+        let mut p = self.p;
+        while p > 0 {
+            let pu = Self::u(p);
+            let next = self.st[pu] as Idx;
+            self.st[pu] = moved(next) as Cell;
+            self.st[pu + 1] = moved(self.st[pu + 1] as Idx) as Cell;
+            p = next;
+        }
+        let mut f = self.f;
+        while f > 0 {
+            let fu = Self::u(f);
+            let next = self.st[fu] as Idx;
+            self.st[fu - 1] = moved(self.st[fu - 1] as Idx) as Cell;
+            self.st[fu] = moved(next) as Cell;
+            f = next;
+        }
+        let mut a = self.e;
+        while a >= 0 {
+            let next = self.st[Self::u(a)] as Idx;
+            self.st[Self::u(a)] = moved(next) as Cell;
+            a = next;
+        }
+
+        self.st
+            .copy_within(Self::u(from)..Self::u(self.s), Self::u(from + delta));
+        self.p = moved(self.p);
+        self.f = moved(self.f);
+        self.h = moved(self.h);
+        self.c = moved(self.c);
+        self.e = moved(self.e);
+        self.s = top;
+        true
+    }
+
+    fn op_include(&mut self) -> Pc {
+        // INCLUDE,path;
+        // The file text is pushed onto the input stream, so it is read as soon
+        // as the call returns to the input (after any enclosing macro bodies).
+        let Some(w) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let Some(path) = self.item_text(w) else {
+            return Pc::Monitor(11);
+        };
+        self.w = w as Cell; // item shown by the monitors below
+
+        let file = match self.resolver.as_mut().map(|r| r.resolve(&path)) {
+            Some(Ok(file)) => file,
+            Some(Err(IncludeError::OutsideRoot)) => return Pc::Monitor(12),
+            Some(Err(_)) | None => return Pc::Monitor(14),
+        };
+        if self.input.is_included(&file.name) {
+            return Pc::Monitor(13);
+        }
+        self.input.include(&file.name, &file.text);
+
+        Pc::EndFn
+    }
+
+    fn op_divert(&mut self) -> Pc {
+        // DIVERT,n;
+        // From now on H=0 output goes to diversion n (0 = output, negative =
+        // discarded). Without an argument output goes back to 0.
+        let Some(w) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let Some(text) = self.item_text(w) else {
+            return Pc::Monitor(11);
+        };
+        self.w = w as Cell; // item shown by Monitor19
+        self.diversion = if text.is_empty() {
+            0
+        } else {
+            match Self::parse_number(&text) {
+                Ok(n) => n,
+                Err(pc) => return pc,
+            }
+        };
+        Pc::EndFn
+    }
+
+    fn op_undivert(&mut self) -> Pc {
+        // UNDIVERT,n;
+        // Diversion n is emptied and its text loaded as the value of the call,
+        // i.e. into the current output (or argument). Without an argument all
+        // positive diversions are flushed in ascending order. The current
+        // diversion is never flushed into itself.
+        let Some(w) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let Some(text) = self.item_text(w) else {
+            return Pc::Monitor(11);
+        };
+        self.w = w as Cell; // item shown by Monitor19
+        let which: Vec<Cell> = if text.is_empty() {
+            self.diversions.keys().copied().filter(|&n| n > 0).collect()
+        } else {
+            match Self::parse_number(&text) {
+                Ok(n) => vec![n],
+                Err(pc) => return pc,
+            }
+        };
+
+        for n in which {
+            if n == self.diversion && self.h == 0 {
+                continue;
+            }
+            let text = self.take_diversion(n);
+            if self.load_text(&text) == Pc::Monitor(11) {
+                return Pc::Monitor(11);
+            }
+        }
+        Pc::EndFn
+    }
+
+    fn op_undef(&mut self) -> Pc {
+        // UNDEF,name;
+        // Unlinks the newest top-level definition of name (one that outlives
+        // every active call) from E. Its cells are reclaimed too, unless it is
+        // a machine macro or its value is still being read by some call.
+        // Undefining a name that has no top-level definition does nothing.
+        let Some(x) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+
+        // prev: the entry whose link points at a (None: E itself)
+        let mut prev: Option<Idx> = None;
+        let mut a = self.e;
+        loop {
+            if a < 0 {
+                return Pc::EndFn;
+            }
+            if self.is_top_level(a) && self.entry_matches(a, x) {
+                break;
+            }
+            prev = Some(a);
+            a = self.st[Self::u(a)] as Idx;
+        }
+
+        let next = self.st[Self::u(a)];
+        match prev {
+            None => self.e = next as Idx,
+            Some(l) => self.st[Self::u(l)] = next,
+        }
+
+        // machine macros have a negative tag where other entries have an item
+        let w = a + 1 + self.st[Self::u(a + 1)] as Idx;
+        let tag = self.st[Self::u(w)];
+        if tag < 0 && tag != MARKER {
+            return Pc::EndFn;
+        }
+        let Some(end) = self.entry_end(a) else {
+            return Pc::Monitor(11);
+        };
+
+        let mut readers = vec![self.c];
+        let mut p = self.p;
+        while p > 0 {
+            readers.push(self.st[Self::u(p + 1)] as Idx);
+            p = self.st[Self::u(p)] as Idx;
+        }
+        if readers.iter().any(|&c| a <= c && c < end) {
+            return Pc::EndFn;
+        }
+
+        if !self.relocate(end, a - end) {
+            return Pc::Monitor(11);
+        }
+        Pc::EndFn
+    }
+
+    fn op_gdef(&mut self) -> Pc {
+        // GDEF,name,value;
+        // Like DEF, but the definition outlives every active call. The entry
+        // is built at the lowest cell of the active frames, after moving them
+        // (and any definitions local to them) up, and is linked into E between
+        // the local definitions and the older ones. Local definitions of the
+        // same name still hide it until their calls return.
+        if let Pc::Monitor(n) = self.resolve_value_params() {
+            return Pc::Monitor(n);
+        }
+        let (Some(name), Some(value)) = (self.arg_cells(1), self.arg_cells(2)) else {
+            return Pc::Monitor(11);
+        };
+
+        let mut base = self.s;
+        for mut x in [self.p, self.f] {
+            while x > 0 {
+                base = base.min(x - 1);
+                x = self.st[Self::u(x)] as Idx;
+            }
+        }
+
+        // prev: the oldest local definition, whose link is the newest global
+        // one (None: there are no local definitions)
+        let mut prev: Option<Idx> = None;
+        let mut head = self.e;
+        while head >= base {
+            prev = Some(head);
+            head = self.st[Self::u(head)] as Idx;
+        }
+
+        let mut entry = vec![head as Cell, name.len() as Cell + 1];
+        entry.extend(name);
+        entry.push(value.len() as Cell + 1);
+        entry.extend(value);
+        entry.push(MARKER);
+        let len = entry.len() as Idx;
+        if !self.relocate(base, len) {
+            return Pc::Monitor(11);
+        }
+        let at = Self::u(base);
+        self.st[at..at + entry.len()].copy_from_slice(&entry);
+
+        match prev {
+            None => self.e = base,
+            Some(l) => self.st[Self::u(l + len)] = base as Cell,
+        }
+        Pc::EndFn
+    }
+
+    fn op_defined(&mut self) -> Pc {
+        // DEFINED,name,yes,no;
+        // Loads yes if name has a definition (local or not), no otherwise.
+        // Like ~n the chosen text is not rescanned; to evaluate a branch,
+        // select a macro name instead: §§DEFINED,X,Then,Else;;
+        let Some(x) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let branch = if self.lookup(x).is_some() { 2 } else { 3 };
+        let Some(y) = self.arg_item(branch) else {
+            return Pc::Monitor(11);
+        };
+        if self.st[Self::u(y)] == MARKER {
+            return Pc::EndFn;
+        }
+        self.load_item(y)
+    }
+
+    fn op_error(&mut self) -> Pc {
+        // ERROR,message;
+        // Raised like the built-in errors: Monitor15 prints the message and
+        // Monitor11 the calls in progress, and the host gets a GpmError.
+        let Some(w) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let Some(message) = self.item_text(w) else {
+            return Pc::Monitor(11);
+        };
+        self.w = w as Cell;
+        let (source, line) = self.input.position();
+        self.errors.push(GpmError {
+            monitor: 15,
+            message,
+            report: String::new(),
+            source: source.to_string(),
+            line,
+        });
+        self.reporting = true;
+        Pc::Monitor(15)
+    }
+
+    fn op_warn(&mut self) -> Pc {
+        // WARN,message;
+        // Only recorded (see take_warnings); expansion carries on.
+        let Some(message) = self.arg_text(1) else {
+            return Pc::Monitor(11);
+        };
+        self.warnings.push(message);
+        Pc::EndFn
+    }
+
+    fn op_case(&mut self, upper: bool) -> Pc {
+        // UPPER,text;  LOWER,text;
+        // Unicode case mapping of each cell of ~1 (which may change the length,
+        // e.g. ß -> SS). Cells that are not characters are loaded unchanged.
+        let Some(text) = self.arg_cells(1) else {
+            return Pc::Monitor(11);
+        };
+        let mut mapped: Vec<Cell> = Vec::new();
+        for cell in text {
+            match char::from_u32(cell as u32) {
+                Some(ch) if upper => mapped.extend(ch.to_uppercase().map(|c| c as Cell)),
+                Some(ch) => mapped.extend(ch.to_lowercase().map(|c| c as Cell)),
+                None => mapped.push(cell),
+            }
+        }
+        self.load_cells(&mapped)
+    }
+
+    fn op_class(&mut self) -> Pc {
+        // CLASS,c,letter,digit,space,other;
+        // Loads one of ~2..~5 according to the first character of ~1, using the
+        // Unicode alphabetic / numeric / white-space properties. An empty ~1
+        // counts as other. The chosen text is not rescanned (cf. DEFINED).
+        let Some(x) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let first = match self.st[Self::u(x)] {
+            len if len > 1 => char::from_u32(self.st[Self::u(x + 1)] as u32),
+            _ => None,
+        };
+        let branch = match first {
+            Some(ch) if ch.is_alphabetic() => 2,
+            Some(ch) if ch.is_numeric() => 3,
+            Some(ch) if ch.is_whitespace() => 4,
+            _ => 5,
+        };
+        let Some(y) = self.arg_item(branch) else {
+            return Pc::Monitor(11);
+        };
+        if self.st[Self::u(y)] == MARKER {
+            return Pc::EndFn;
+        }
+        self.load_item(y)
+    }
+
+    fn op_index(&mut self) -> Pc {
+        // INDEX,haystack,needle;
+        // Position (counted in characters from 0) of the first occurrence of
+        // ~2 in ~1, or -1. An empty needle is found at 0.
+        let (Some(hay), Some(needle)) = (self.arg_cells(1), self.arg_cells(2)) else {
+            return Pc::Monitor(11);
+        };
+        let pos = if needle.is_empty() {
+            Some(0)
+        } else {
+            hay.windows(needle.len()).position(|w| w == needle.as_slice())
+        };
+        match pos {
+            Some(k) => self.load_text(&k.to_string()),
+            None => self.load_text("-1"),
+        }
+    }
+
+    fn op_replace(&mut self) -> Pc {
+        // REPLACE,text,from,to;
+        // Every occurrence of ~2 in ~1 (left to right, not overlapping) is
+        // replaced by ~3. The result is loaded, not rescanned.
+        let (Some(text), Some(from), Some(to)) =
+            (self.arg_cells(1), self.arg_cells(2), self.arg_cells(3))
+        else {
+            return Pc::Monitor(11);
+        };
+        if from.is_empty() {
+            return self.load_cells(&text);
+        }
+        let mut out: Vec<Cell> = Vec::with_capacity(text.len());
+        let mut k = 0;
+        while k < text.len() {
+            if text[k..].starts_with(&from) {
+                out.extend_from_slice(&to);
+                k += from.len();
+            } else {
+                out.push(text[k]);
+                k += 1;
+            }
+        }
+        self.load_cells(&out)
+    }
+
+    fn op_translit(&mut self) -> Pc {
+        // TRANSLIT,text,from-set,to-set;
+        // As in m4: each character of ~1 that occurs in ~2 is replaced by the
+        // character at the same place in ~3, or dropped if ~3 is shorter.
+        // The first occurrence in ~2 counts; there are no ranges.
+        let (Some(text), Some(from), Some(to)) =
+            (self.arg_cells(1), self.arg_cells(2), self.arg_cells(3))
+        else {
+            return Pc::Monitor(11);
+        };
+        let out: Vec<Cell> = text
+            .iter()
+            .filter_map(|c| match from.iter().position(|f| f == c) {
+                Some(k) => to.get(k).copied(),
+                None => Some(*c),
+            })
+            .collect();
+        self.load_cells(&out)
+    }
+
+    fn op_var(&mut self) -> Pc {
+        // VAR,name;
+        // Value of the host variable set by set_var; an unknown name is
+        // reported like an undefined macro (Monitor7).
+        let Some(w) = self.arg_item(1) else {
+            return Pc::Monitor(11);
+        };
+        let Some(name) = self.item_text(w) else {
+            return Pc::Monitor(11);
+        };
+        match self.vars.get(&name) {
+            Some(value) => {
+                let value = value.clone();
+                self.load_text(&value)
+            }
+            None => {
+                self.w = w as Cell;
+                Pc::Monitor(7)
+            }
+        }
+    }
+
+    fn op_gensym(&mut self) -> Pc {
+        // GENSYM,prefix;
+        // prefix followed by the next value of a VM-wide counter (from 1), so
+        // no two calls yield the same symbol. Unlike the UPDATE counter idiom
+        // this needs no pre-allocated definition and never runs out of room.
+        let Some(prefix) = self.arg_text(1) else {
+            return Pc::Monitor(11);
+        };
+        self.gensym += 1;
+        let sym = format!("{}{}", prefix, self.gensym);
+        self.load_text(&sym)
+    }
+
+    fn op_changecc(&mut self) -> Pc {
+        // CHANGECC,role,char;
+        // role is a ControlChars field name: open, close, def, arg_sep, apply,
+        // load_arg or comment (which an empty char switches off).
+        //
+        // The call itself has been scanned by the time it is applied, so the
+        // change takes effect from the next character read. That is always at
+        // q = 1 (calls are never applied inside quotes), so op_q2 only ever
+        // counts with one pair of quotes. Text already in the store keeps its
+        // characters: macro bodies defined earlier are read with the new set
+        // when they are called. Roles must stay distinct, otherwise Monitor16.
+        let (Some(w), Some(ch)) = (self.arg_item(1), self.arg_cells(2)) else {
+            return Pc::Monitor(11);
+        };
+        let Some(role) = self.item_text(w) else {
+            return Pc::Monitor(11);
+        };
+        self.w = w as Cell;
+
+        let ch = match ch.as_slice() {
+            [] => None,
+            [c] if *c >= 0 => Some(*c),
+            _ => return Pc::Monitor(16),
+        };
+        let mut cc = self.cc;
+        match (role.as_str(), ch) {
+            ("comment", c) => cc.comment = c,
+            ("open", Some(c)) => cc.open = c,
+            ("close", Some(c)) => cc.close = c,
+            ("def", Some(c)) => cc.def = c,
+            ("arg_sep", Some(c)) => cc.arg_sep = c,
+            ("apply", Some(c)) => cc.apply = c,
+            ("load_arg", Some(c)) => cc.load_arg = c,
+            _ => return Pc::Monitor(16),
+        }
+
+        let mut all: Vec<Cell> = [cc.open, cc.close, cc.def, cc.arg_sep, cc.apply, cc.load_arg]
+            .into_iter()
+            .chain(cc.comment)
+            .collect();
+        let n = all.len();
+        all.sort_unstable();
+        all.dedup();
+        if all.len() != n {
+            return Pc::Monitor(16);
+        }
+
+        self.cc = cc;
+        Pc::EndFn
+    }
+
+    fn op_foreach(&mut self) -> Pc {
+        // FOREACH,template,item1,item2,...;
+        // The template is expanded once per item, with ~1 or ~{1} standing
+        // for the item and ~2 or ~{2} for its index (from 1). Only references
+        // outside nested quotes are bound, so an inner FOREACH keeps its own;
+        // any other reference there would read FOREACH's own frame, and is
+        // Monitor3 instead.
+        //
+        // The copies are laid out after the frame's Marker and read as if
+        // they were the body of FOREACH: ~1 becomes ~{k} for the item's own
+        // position k in this frame, so items are loaded like any argument and
+        // never rescanned. The frame grows to cover the body, and EndFn
+        // removes it with the items when the last copy has been read.
+        let (Some(template), Some(n)) = (self.arg_cells(1), self.arg_count()) else {
+            return Pc::Monitor(11);
+        };
+
+        let mut body = Vec::new();
+        for i in 1..n {
+            let mut depth = 0;
+            let mut k = 0;
+            while k < template.len() {
+                let x = template[k];
+                if x == self.cc.open {
+                    depth += 1;
+                } else if x == self.cc.close && depth > 0 {
+                    depth -= 1;
+                } else if depth == 0 && x == self.cc.load_arg {
+                    // the reference and the cells it takes up
+                    let reference = match template.get(k + 1) {
+                        Some(&d) if d == '{' as Cell => {
+                            let digits = &template[k + 2..];
+                            let close = digits.iter().position(|&c| c == '}' as Cell);
+                            close.map(|j| (&digits[..j], j + 3))
+                        }
+                        Some(d) => Some((core::slice::from_ref(d), 2)),
+                        None => None,
+                    };
+                    match reference {
+                        Some((r, used)) if r == ['1' as Cell] => {
+                            body.extend([x, '{' as Cell]);
+                            body.extend((i + 1).to_string().chars().map(|c| c as Cell));
+                            body.push('}' as Cell);
+                            k += used;
+                        }
+                        Some((r, used)) if r == ['2' as Cell] => {
+                            body.extend(i.to_string().chars().map(|c| c as Cell));
+                            k += used;
+                        }
+                        _ => return Pc::Monitor(3),
+                    }
+                    continue;
+                }
+                body.push(x);
+                k += 1;
+            }
+        }
+
+        let start = self.s;
+        let len = body.len() as Idx + 1;
+        if Self::u(start + len) > self.mem_size {
+            return Pc::Monitor(11);
+        }
+        let at = Self::u(start);
+        self.st[at..at + body.len()].copy_from_slice(&body);
+        self.st[at + body.len()] = MARKER;
+        self.s += len;
+        self.st[Self::u(self.p - 1)] += len as Cell;
+        if self.h != 0 {
+            self.st[Self::u(self.h)] += len as Cell;
+        }
+
+        self.c = start;
+        Pc::Start
+    }
+
+    fn step(&mut self) {
+        let from = self.pc;
+        let next = match self.pc {
+            // main cycle
+            Pc::Start => self.op_start(),
+            Pc::Copy => self.op_copy(),
+            Pc::Scan => self.op_scan(),
+            Pc::Q2 => self.op_q2(),
+            Pc::Comment => self.op_comment(),
+
+            // warning char actions
+            Pc::Fn => self.op_fn(),
+            Pc::NextItem => self.op_next_item(),
+            Pc::Apply => self.op_apply(),
+            Pc::LoadArg => self.op_load_arg(),
+            Pc::ArgRef => self.op_arg_ref(),
+            Pc::EndFn => self.op_end_fn(),
+            Pc::Exit => self.op_exit(),
+
+            // machine code macros
+            Pc::DEF => self.op_def(),
+            Pc::VAL => self.op_val(),
+            Pc::UPDATE => self.op_update(),
+            Pc::BIN => self.op_bin(),
+            Pc::DEC => self.op_dec(),
+            Pc::BAR => self.op_bar(),
+            Pc::INCLUDE => self.op_include(),
+            Pc::DIVERT => self.op_divert(),
+            Pc::UNDIVERT => self.op_undivert(),
+            Pc::UNDEF => self.op_undef(),
+            Pc::DEFINED => self.op_defined(),
+            Pc::ERROR => self.op_error(),
+            Pc::WARN => self.op_warn(),
+            Pc::UPPER => self.op_case(true),
+            Pc::LOWER => self.op_case(false),
+            Pc::CLASS => self.op_class(),
+            Pc::INDEX => self.op_index(),
+            Pc::REPLACE => self.op_replace(),
+            Pc::TRANSLIT => self.op_translit(),
+            Pc::VAR => self.op_var(),
+            Pc::GENSYM => self.op_gensym(),
+            Pc::CHANGECC => self.op_changecc(),
+            Pc::FOREACH => self.op_foreach(),
+            Pc::GDEF => self.op_gdef(),
+
+            Pc::Monitor(n) => self.monitor(n),
+            Pc::Finish => panic!("Finish"),
+            Pc::NoInput => panic!("NoInput"),
+        };
+
+        // input ran out half way: the next run starts by redoing this state
+        // (every state that reads input does so before changing anything)
+        if next == Pc::NoInput {
+            self.resume = self.pc;
+        }
+        self.pc = next;
+
+        // a report may leave the store half changed (Monitor4 clears H) until
+        // Monitor11 abandons the calls, so only its end is checked
+        if cfg!(debug_assertions) && self.step_checks && !matches!(next, Pc::Monitor(_)) {
+            if let Err(e) = self.check_invariants() {
+                panic!("store invariant broken by {:?} (now at {:?}): {}", from, next, e);
+            }
+        }
+    }
+
+    // CPL Write['...'] with *n/*t/*s escapes
+    fn write_text(&mut self, s: &str) {
+        let mut it = s.chars().peekable();
+        while let Some(ch) = it.next() {
+            if ch == '*' {
+                match it.peek().copied() {
+                    Some('n') => {
+                        it.next();
+                        self.write_symbol('\n' as Cell);
+                    }
+                    Some('t') => {
+                        it.next();
+                        self.write_symbol('\t' as Cell);
+                    }
+                    Some('s') => {
+                        it.next();
+                        self.write_symbol(' ' as Cell);
+                    }
+                    _ => self.write_symbol('*' as Cell),
+                }
+            } else {
+                self.write_symbol(ch as u32 as Cell);
+            }
+        }
+    }
+
+    // Appendix 2 §3 Item[x]
+    fn item(&mut self, x: Idx) {
+        // save A,H
+        let a0 = self.a;
+        let h0 = self.h;
+        self.h = 0;
+
+        if x < 0 || Self::u(x) >= self.mem_size {
+            self.write_text("*n(Item: bad pointer)");
+            self.a = a0;
+            self.h = h0;
+            return;
+        }
+
+        let stx = self.st[Self::u(x)] as Idx;
+
+        // if ST[x]=0 → incomplete object
+        let end_k: Idx = if stx == 0 {
+            (self.s - x - 1).max(0)
+        } else {
+            (stx - 1).max(0)
+        };
+
+        for k in 1..=end_k {
+            let idx = x + k;
+            if idx < 0 || Self::u(idx) >= self.mem_size {
+                break;
+            }
+            self.a = self.st[Self::u(idx)];
+            self.write_symbol(self.a); // Load with H=0, bypassing any diversion
+        }
+
+        if stx == 0 {
+            self.write_text("...*t(Incomplete)");
         }
 
         // restore A,H
@@ -1157,7 +2660,75 @@ impl GpmVm {
         self.h = h0;
     }
 
+    // One report per error: it starts at the first monitor entered and ends
+    // when a monitor hands control back to the main cycle (so Monitor7 followed
+    // by Monitor11 is a single report).
     fn monitor(&mut self, nr: u8) -> Pc {
+        if !self.reporting {
+            self.reporting = true;
+            let (source, line) = self.input.position();
+            self.errors.push(GpmError {
+                monitor: nr,
+                message: String::new(),
+                report: String::new(),
+                source: source.to_string(),
+                line,
+            });
+        }
+
+        // Monitor11 walks P and F down to 0, so the frames are found first
+        let base = (nr == 11).then(|| self.call_base());
+        let next = self.monitor_entry(nr);
+        if let Some(base) = base {
+            self.abandon_calls(base);
+        }
+
+        if !matches!(next, Pc::Monitor(_)) {
+            self.reporting = false;
+            if let Some(e) = self.errors.last_mut() {
+                if e.message.is_empty() {
+                    let first = e.report.trim_start_matches('\n').lines().next();
+                    e.message = match first.and_then(|l| l.strip_prefix("MONITOR:")) {
+                        Some(m) => m.trim().to_string(),
+                        None => "Irremediable error".to_string(),
+                    };
+                }
+            }
+        }
+        next
+    }
+
+    // Lowest cell of the calls in progress (S if there are none)
+    fn call_base(&self) -> Idx {
+        let mut base = self.s;
+        for mut x in [self.p, self.f] {
+            while x > 0 && Self::u(x) < self.mem_size {
+                base = base.min(x - 1);
+                x = self.st[Self::u(x)] as Idx;
+            }
+        }
+        base
+    }
+
+    // Not in Appendix 2, on purpose. Monitor11 there ends with P = F = 0 but
+    // leaves C, H, S and q as they were. After an error in a macro body Start
+    // would read on through the rest of that body (and past its Marker) with
+    // the frames gone; after one in an argument list it would go on loading
+    // into the abandoned argument. The abandoned calls are dropped here
+    // instead, with any definitions made during them, and reading goes on
+    // with the input. With no calls in progress this changes nothing.
+    fn abandon_calls(&mut self, base: Idx) {
+        self.h = 0;
+        self.c = 0;
+        self.q = 1;
+        self.s = base;
+        while self.e >= base {
+            self.e = self.st[Self::u(self.e)] as Idx;
+        }
+    }
+
+    #[allow(clippy::assign_op_pattern)]
+    fn monitor_entry(&mut self, nr: u8) -> Pc {
         match nr {
             0 => Pc::Monitor(11), // unused
             1 => {
@@ -1184,7 +2755,15 @@ impl GpmVm {
                 // Monitor4: Not enough arguments supplied in call
                 self.write_text("*nMONITOR: No argument ");
                 self.h = 0;
-                self.load(); // outputs current A (argument designator)
+                if self.arg_ref.is_empty() {
+                    self.write_symbol(self.a); // H := 0 Load: outputs current A (argument designator)
+                } else {
+                    // ~{n}: the whole designator
+                    let digits = core::mem::take(&mut self.arg_ref);
+                    self.write_symbol('{' as Cell);
+                    digits.into_iter().for_each(|x| self.write_symbol(x));
+                    self.write_symbol('}' as Cell);
+                }
                 self.write_text("*n in call for ");
                 self.item(self.p + 2);
                 Pc::Monitor(11)
@@ -1286,7 +2865,11 @@ impl GpmVm {
 
                 self.write_text("*nEnd of monitor printing");
                 self.a = 'Q' as Cell;
-                self.load();
+                if self.h == 0 {
+                    self.write_symbol(self.a); // part of the diagnostic, never diverted
+                } else {
+                    self.load();
+                }
 
                 // go to P>F -> EndFn, Start
                 if self.p > self.f {
@@ -1295,31 +2878,111 @@ impl GpmVm {
                     Pc::Start
                 }
             }
-            _ => unreachable!(" only 11 monitors exists"),
+            12 => {
+                // Monitor12: INCLUDE path leaves the resolver's root. Terminate.
+                self.write_text("*nMONITOR: Include path outside root ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            13 => {
+                // Monitor13: INCLUDE of a file that is still being read. Terminate.
+                self.write_text("*nMONITOR: Recursive include of ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            14 => {
+                // Monitor14: INCLUDE file missing or unreadable. Terminate.
+                self.write_text("*nMONITOR: Cannot include ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            15 => {
+                // Monitor15: ERROR,message; from a macro. Terminate.
+                self.write_text("*nMONITOR: ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            16 => {
+                // Monitor16: CHANGECC with an unknown role, a missing or
+                // multi-character char, or one already used by another role.
+                self.write_text("*nMONITOR: Impossible control character change for ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            17 => {
+                // Monitor17: DEF value whose first line starts ~< but is not
+                // a list of distinct parameter names with trailing defaults.
+                self.write_text("*nMONITOR: Impossible parameter list in definition of ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            18 => {
+                // Monitor18: call of a macro with a parameter header with
+                // fewer arguments than it requires or more than it declares.
+                self.write_text("*nMONITOR: Wrong number of arguments in call for ");
+                self.item(self.p + 2);
+                if let (Some(n), Some(params)) = (self.arg_count(), self.arity(self.w as Idx)) {
+                    let max = params.max;
+                    let min = max - params.defaults.len();
+                    let expected = if min == max {
+                        max.to_string()
+                    } else {
+                        format!("{} to {}", min, max)
+                    };
+                    self.write_text(&format!("*n{} given, {} expected", n, expected));
+                }
+                Pc::Monitor(11)
+            }
+            19 => {
+                // Monitor19: DIVERT/UNDIVERT number that does not fit in a
+                // cell. Terminate.
+                self.write_text("*nMONITOR: Number out of range ");
+                self.item(self.w as Idx);
+                Pc::Monitor(11)
+            }
+            _ => unreachable!(" only 19 monitors exists"),
         }
     }
 
     pub fn run(&mut self, input: &str) -> String {
-        let input = String::from(input);
-        self.input = input.chars().rev().collect();
-        self.pc = Pc::Start;
+        self.input.feed(input);
+        self.pc = self.resume;
+        self.resume = Pc::Start;
         while self.pc != Pc::Finish && self.pc != Pc::NoInput {
             self.step();
         }
+        #[allow(clippy::match_single_binding)]
         match self.pc {
             _ => {}
         }
-        let output = self.output.clone();
-        self.output = "".to_string();
-        output
+        // an unmatched > at the top level ends this input, whatever is left
+        if self.pc == Pc::Finish {
+            self.input.discard();
+        }
+        self.flush_sinks();
+        core::mem::take(&mut self.output)
+    }
+
+    // run, but a monitor raised while reading this input is returned as an
+    // error (the first one, if several) instead of being buried in the output.
+    pub fn try_run(&mut self, input: &str) -> Result<String, GpmError> {
+        let before = self.errors.len();
+        let output = self.run(input);
+        match self.errors.get(before) {
+            Some(e) => Err(e.clone()),
+            None => Ok(output),
+        }
     }
 
     fn is_stable(&self) -> bool {
         self.q == 1 && self.h == 0 && self.c == 0 && self.p == 0 && self.f == 0
     }
 
+    // End of the input. Text pushed with push_input and not read by a run
+    // since is dropped unread, so that it cannot turn up in the next run.
     pub fn end(&mut self) -> String {
         self.output.clear();
+        self.input.discard();
 
         if self.is_stable() {
             self.pc = Pc::Finish;
@@ -1331,10 +2994,89 @@ impl GpmVm {
         self.h = 0;
         self.pc = Pc::Monitor(11);
         let _ = self.monitor(11);
+        self.reporting = false;
         self.pc = Pc::Finish;
 
-        let output = self.output.clone();
-        self.output.clear();
-        output
+        self.flush_sinks();
+        core::mem::take(&mut self.output)
+    }
+}
+
+// The sink in slot if it is a T (otherwise it stays where it is)
+fn take_sink<T: OutputSink>(slot: &mut Option<Box<dyn OutputSink>>) -> Option<T> {
+    if !slot.as_deref().is_some_and(|s| s.as_any().is::<T>()) {
+        return None;
+    }
+    slot.take()?.into_any().downcast::<T>().ok().map(|sink| *sink)
+}
+
+// check_invariants against a store corrupted on purpose, which the public
+// API cannot produce (tests/invariants.rs covers the consistent cases)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stopped in the body of Y while collecting the arguments of Z, inside
+    // those of X: an entered call (P), two calls not yet entered (F) and a
+    // definition (E).
+    fn mid_call() -> GpmVm {
+        let mut vm = GpmVm::new(ControlChars::default(), 10_000);
+        vm.input.feed("§DEF,Y,<§X,§Z,~1;;>;§Y,a;");
+        vm.pc = Pc::Start;
+        while !(vm.p != 0 && vm.f > vm.p && vm.st[GpmVm::u(vm.f)] > vm.p) {
+            assert!(vm.pc != Pc::Finish && vm.pc != Pc::NoInput);
+            vm.step();
+        }
+        assert_eq!(vm.check_invariants(), Ok(()));
+        vm
+    }
+
+    fn corrupt(change: impl Fn(&mut GpmVm)) -> Result<(), String> {
+        let mut vm = mid_call();
+        change(&mut vm);
+        vm.check_invariants()
+    }
+
+    #[test]
+    fn broken_registers() {
+        assert!(corrupt(|vm| vm.s = vm.mem_size as Idx + 1).is_err());
+        assert!(corrupt(|vm| vm.s = -1).is_err());
+        assert!(corrupt(|vm| vm.q = 0).is_err());
+        assert!(corrupt(|vm| vm.h = vm.s).is_err());
+    }
+
+    #[test]
+    fn broken_p_chain() {
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.p)] = vm.p).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.p)] = Cell::MAX).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.p - 1)] = Cell::MAX).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.p + 2)] = Cell::MAX).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.p + 2)] = 0).is_err());
+    }
+
+    #[test]
+    fn broken_f_chain() {
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.f)] = vm.f).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.f)] = Cell::MIN).is_err());
+        // the saved H of the newest call bounds the items of the one before
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.f - 1)] = Cell::MAX).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.f - 1)] = -7).is_err());
+        // the name item of X (that of Z is still being collected)
+        let x_name = |vm: &mut GpmVm, len: Cell| {
+            let x = vm.st[GpmVm::u(vm.f)] + 2;
+            vm.st[GpmVm::u(x)] = len;
+        };
+        assert!(corrupt(|vm| x_name(vm, Cell::MAX)).is_err());
+        assert!(corrupt(|vm| x_name(vm, 0)).is_err());
+    }
+
+    #[test]
+    fn broken_e_chain() {
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.e)] = vm.e).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.e)] = Cell::MIN).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.e + 1)] = Cell::MAX).is_err());
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.e + 1)] = 0).is_err());
+        // the value item, after the name Y
+        assert!(corrupt(|vm| vm.st[GpmVm::u(vm.e + 3)] = Cell::MAX).is_err());
     }
 }
@@ -0,0 +1,61 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+const ROW: &str = "§DEF,Row,<~{1}|~{02}|~{10}|~{12}>;";
+
+#[test]
+fn braced_references_reach_past_nine() {
+    let mut vm = vm();
+    assert_eq!(vm.run(ROW), "");
+
+    let out = vm.run("§Row,a,b,c,d,e,f,g,h,i,j,k,l;");
+    assert_eq!(out, "a|b|j|l");
+    // the one-digit form is unchanged
+    assert_eq!(vm.run("§DEF,X,<~1~{1}~0>;§X,y;"), "yyX");
+}
+
+#[test]
+fn argument_count() {
+    let mut vm = vm();
+
+    let out = vm.run("§DEF,N,<~#>;§N;/§N,a;/§N,,;/§N,a,b,c,d,e,f,g,h,i,j,k,l;");
+    assert_eq!(out, "0/1/2/12");
+}
+
+#[test]
+fn missing_braced_argument_raises_monitor4() {
+    let mut vm = vm();
+    let _ = vm.run(ROW);
+
+    let err = vm.try_run("§Row,a,b,c;").unwrap_err();
+    assert_eq!(err.monitor, 4);
+    assert!(err.report.starts_with("\nMONITOR: No argument {10}\n in call for Row\n"), "{:?}", err.report);
+
+    // and a plain ~n afterwards still reports its own designator
+    let _ = vm.run("§DEF,Two,<~2>;");
+    let err = vm.try_run("§Two,a;").unwrap_err();
+    assert!(err.report.starts_with("\nMONITOR: No argument 2\n"), "{:?}", err.report);
+}
+
+#[test]
+fn bad_braced_reference_raises_monitor3() {
+    let mut vm = vm();
+
+    for body in ["~{}", "~{x}", "~{-1}", "~{1"] {
+        let err = vm.try_run(&format!("§DEF,Bad,<{}>;§Bad,a;", body)).unwrap_err();
+        assert_eq!(err.monitor, 3, "{}", body);
+    }
+}
+
+#[test]
+fn braced_references_pass_arguments_on() {
+    let mut vm = vm();
+    let _ = vm.run("§DEF,Ten,<[~{10}]>;§DEF,Pass,<§Ten,~1,~2,~3,~4,~5,~6,~7,~8,~9,~{10};>;");
+
+    assert_eq!(vm.run("§Pass,1,2,3,4,5,6,7,8,9,X;"), "[X]");
+    assert_eq!(vm.run("§Ten,1,2,3,4,5,6,7,8,9,"), "");
+    assert_eq!(vm.run("ten;"), "[ten]");
+}
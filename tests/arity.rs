@@ -0,0 +1,95 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+const LINK: &str = "§DEF,Link,<~<url, title = Home>\n[~{title}](~{url})>;";
+
+#[test]
+fn missing_arguments_take_their_defaults() {
+    let mut vm = vm();
+    assert_eq!(vm.run(LINK), "");
+
+    assert_eq!(vm.run("§Link,x.html;"), "[Home](x.html)");
+    assert_eq!(vm.run("§Link,y.html,Why;"), "[Why](y.html)");
+    // an empty argument is still an argument
+    assert_eq!(vm.run("§Link,z.html,;"), "[](z.html)");
+    assert!(vm.errors().is_empty());
+}
+
+#[test]
+fn defaults_are_real_arguments() {
+    let mut vm = vm();
+
+    let out = vm.run("§DEF,D,<~<a, b=2, c=three>\n~#:~1~2~3>;§D,1;/§D,1,x;");
+    assert_eq!(out, "3:12three/3:1xthree");
+}
+
+#[test]
+fn defaults_inside_an_argument_list() {
+    let mut vm = vm();
+    let _ = vm.run(LINK);
+
+    let out = vm.run("§DEF,Wrap,<(~1)>;§Wrap,§Link,a;!;");
+    assert_eq!(out, "([Home](a)!)");
+}
+
+#[test]
+fn wrong_argument_count_raises_monitor18_before_the_body() {
+    let mut vm = vm();
+    let _ = vm.run("§DEF,Two,<~<a,b>\nbody~{a}~{b}>;");
+
+    let out = vm.run("§Two,1;");
+    assert!(!out.contains("body"), "{out:?}");
+    let err = &vm.take_errors()[0];
+    assert_eq!(err.monitor, 18);
+    assert!(
+        err.report.starts_with("\nMONITOR: Wrong number of arguments in call for Two\n1 given, 2 expected\n"),
+        "{:?}",
+        err.report
+    );
+
+    let _ = vm.run(LINK);
+    let err = vm.try_run("§Link,a,b,c;").unwrap_err();
+    assert_eq!(err.monitor, 18);
+    assert!(err.report.contains("3 given, 1 to 2 expected"), "{:?}", err.report);
+    let err = vm.try_run("§Link;").unwrap_err();
+    assert!(err.report.contains("0 given, 1 to 2 expected"), "{:?}", err.report);
+
+    // the VM carries on afterwards
+    assert_eq!(vm.run("§Two,x,y;"), "bodyxy");
+}
+
+#[test]
+fn update_keeps_the_header() {
+    let mut vm = vm();
+    let _ = vm.run("§DEF,L,<~<a,b=2>\n[~{a}~{b}]>;");
+
+    // the new text replaces the body only; the defaults still apply
+    assert_eq!(vm.run("§UPDATE,L,<(~1~2)>;§L,x;§L,x,y;"), "(x2)(xy)");
+    assert_eq!(vm.run("§VAL,L;"), "(~1~2)");
+    assert_eq!(vm.run("§UPDATE,L,<~1>;§L,a;§L,a,b;"), "aa");
+    assert!(vm.errors().is_empty());
+
+    // the room is the body's, not the header's as well
+    let err = vm.try_run("§UPDATE,L,<[~{1}~{2}]!>;").unwrap_err();
+    assert_eq!(err.monitor, 9);
+    assert_eq!(vm.run("§VAL,L;"), "~1");
+}
+
+#[test]
+fn macros_without_a_header_are_not_checked() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§DEF,P,<~1>;§P,a,b,c;"), "a");
+    assert!(vm.errors().is_empty());
+}
+
+#[test]
+fn required_parameter_after_a_default_raises_monitor17() {
+    let mut vm = vm();
+
+    let err = vm.try_run("§DEF,B,<~<a=1, b>\nx>;").unwrap_err();
+    assert_eq!(err.monitor, 17);
+}
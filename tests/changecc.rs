@@ -0,0 +1,69 @@
+use gpm_in_rust::{Cell, ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn changecc_switches_quotes_for_the_rest_of_the_input() {
+    let mut vm = vm();
+
+    let out = vm.run("§CHANGECC,open,[;§CHANGECC,close,];[<b>a, b</b>] <[x]>");
+    assert_eq!(out, "<b>a, b</b> <x>");
+    assert_eq!(vm.control_chars().open, '[' as Cell);
+
+    // nested quotes are counted with the new pair
+    assert_eq!(vm.run("[[x] <y>]"), "[x] <y>");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn changecc_from_inside_a_body() {
+    let mut vm = vm();
+
+    // C output: the quotes change while Html runs and are put back by its
+    // last call, which is itself scanned with the new characters
+    let out = vm.run(concat!(
+        "§DEF,Html,<§CHANGECC,open,{;§CHANGECC,close,};{<p>}~1{</p>}§CHANGECC,open,<;§CHANGECC,close,>;>;",
+        "§Html,text; <q>",
+    ));
+    assert_eq!(out, "<p>text</p> q");
+}
+
+#[test]
+fn changed_chars_apply_to_earlier_definitions() {
+    let mut vm = vm();
+
+    let out = vm.run(concat!(
+        "§DEF,Two,<@1/@2>;§Two,a,b;|",
+        "§CHANGECC,load_arg,@;§Two,a,b;|",
+        "§CHANGECC,def,%;%CHANGECC,arg_sep,:;%Two:x:y;",
+    ));
+    assert_eq!(out, "@1/@2|a/b|x/y");
+}
+
+#[test]
+fn changecc_comment_can_be_switched_on_and_off() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§CHANGECC,comment,#;a # gone\nb"), "a b");
+    assert_eq!(vm.run("§CHANGECC,comment,;a # kept"), "a # kept");
+}
+
+#[test]
+fn impossible_change_raises_monitor16() {
+    let mut vm = vm();
+
+    for input in [
+        "§CHANGECC,open,<§>;",
+        "§CHANGECC,apply,<,>;",
+        "§CHANGECC,open,;",
+        "§CHANGECC,open,<[[>;",
+        "§CHANGECC,quote,[;",
+    ] {
+        let err = vm.try_run(input).unwrap_err();
+        assert_eq!(err.monitor, 16, "{}", input);
+        assert!(err.message.starts_with("Impossible control character change for "));
+    }
+    assert_eq!(vm.control_chars(), ControlChars::default());
+}
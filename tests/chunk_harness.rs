@@ -41,3 +41,18 @@ fn chunk_harness_z_dawnego_main() {
     let end = vm.end();
     assert_eq!(end, "");
 }
+
+#[test]
+fn a_quote_can_close_at_the_start_of_the_next_chunk() {
+    // the first chunk ends inside the quote, so the > is read in the quote
+    // state rather than as an unmatched >
+    for (chunks, expected) in [
+        (["§UPPER,<ab", ">;"], "AB"),
+        (["§DEF,A,<x", ">;§A;"], "x"),
+        (["<ab", ">c"], "abc"),
+    ] {
+        let mut vm = GpmVm::new(ControlChars::default(), 10_000);
+        let out: String = chunks.iter().map(|c| vm.run(c)).collect();
+        assert_eq!(out + &vm.end(), expected, "{:?}", chunks);
+    }
+}
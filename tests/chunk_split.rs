@@ -0,0 +1,56 @@
+use gpm_in_rust::{ControlChars, GpmVm, ProgramGenerator};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 50_000)
+}
+
+// Output of the chunks run one after another, then end()
+fn expand(chunks: &[&str]) -> String {
+    let mut vm = vm();
+    let mut out: String = chunks.iter().map(|c| vm.run(c)).collect();
+    out.push_str(&vm.end());
+    out
+}
+
+#[test]
+fn generator_is_deterministic() {
+    let a = ProgramGenerator::new(7).program(20);
+    let b = ProgramGenerator::new(7).program(20);
+    assert_eq!(a, b);
+    assert_ne!(a, ProgramGenerator::new(8).program(20));
+}
+
+#[test]
+fn generated_programs_do_real_work() {
+    // not just monitors: most programs expand without any error
+    let mut clean = 0;
+    for seed in 0..50 {
+        let program = ProgramGenerator::new(seed).program(12);
+        let mut vm = vm();
+        let _ = vm.run(&program);
+        let _ = vm.end();
+        if vm.errors().is_empty() {
+            clean += 1;
+        }
+    }
+    assert!(clean >= 25, "{clean}");
+}
+
+#[test]
+fn output_does_not_depend_on_where_input_is_split() {
+    for seed in 0..60 {
+        let program = ProgramGenerator::new(seed).program(12);
+        let whole = expand(&[&program]);
+
+        let bounds: Vec<usize> = program.char_indices().map(|(i, _)| i).skip(1).collect();
+        for &k in &bounds {
+            let (head, tail) = program.split_at(k);
+            assert_eq!(expand(&[head, tail]), whole, "seed {seed}, split at {k}: {program:?}");
+        }
+
+        // and one character at a time
+        let chars: Vec<String> = program.chars().map(String::from).collect();
+        let chunks: Vec<&str> = chars.iter().map(String::as_str).collect();
+        assert_eq!(expand(&chunks), whole, "seed {seed}, per character: {program:?}");
+    }
+}
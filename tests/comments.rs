@@ -0,0 +1,77 @@
+use gpm_in_rust::{Cell, ControlChars, GpmVm};
+
+fn vm(cc: ControlChars) -> GpmVm {
+    GpmVm::new(cc, 10_000)
+}
+
+fn with_comments() -> ControlChars {
+    ControlChars { comment: Some('#' as Cell), ..ControlChars::default() }
+}
+
+#[test]
+fn comment_runs_to_end_of_line() {
+    let mut vm = vm(with_comments());
+
+    let out = vm.run("# header\n§DEF,Two,2;# trailing, with ; and §\n[§Two;]# last\n");
+    assert_eq!(out, "[2]");
+    // quoted text is copied as it stands
+    assert_eq!(vm.run("<# not a comment>\n"), "# not a comment\n");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn comment_inside_call_and_body() {
+    let mut vm = vm(with_comments());
+
+    let out = vm.run(concat!(
+        "§DEF,Pair,<(~1 # first\n,~2) # second>;\n",
+        "§Pair, # arguments may be commented\na,b;",
+    ));
+    // the blanks before each # belong to the text
+    assert_eq!(out, "\n( a ,b) ");
+}
+
+#[test]
+fn comment_spans_chunks() {
+    let mut vm = vm(with_comments());
+
+    assert_eq!(vm.run("a# one"), "a");
+    assert_eq!(vm.run(" two, three;"), "");
+    assert_eq!(vm.run(" four\nb"), "b");
+}
+
+#[test]
+fn comments_are_off_by_default() {
+    let mut vm = vm(ControlChars::default());
+    assert_eq!(vm.run("# kept\n"), "# kept\n");
+}
+
+#[test]
+fn discard_newline_after_top_level_call() {
+    let mut vm = vm(with_comments());
+    vm.set_discard_newline(true);
+
+    let out = vm.run(concat!(
+        "§DEF,A,<alpha>;\n",
+        "§DEF,B,<[§A;\n]>;\n",
+        "\n",
+        "§B;\n",
+        "x §A; y\n",
+    ));
+    // only the newline straight after a top-level call goes; the one in the
+    // body of B and the blank line stay
+    assert_eq!(out, "\n[alpha\n]x alpha y\n");
+
+    // also when the newline arrives in the next chunk
+    assert_eq!(vm.run("§A;"), "alpha");
+    assert_eq!(vm.run("\nz"), "z");
+}
+
+#[test]
+fn chunk_boundary_inside_quotes_keeps_quoting() {
+    let mut vm = vm(ControlChars::default());
+
+    assert_eq!(vm.run("<a"), "a");
+    assert_eq!(vm.run(",b;>"), ",b;");
+    assert_eq!(vm.end(), "");
+}
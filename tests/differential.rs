@@ -0,0 +1,160 @@
+use gpm_in_rust::{
+    first_divergence, ControlChars, Divergence, GpmError, GpmVm, MacroEngine, ProgramGenerator,
+    ReferenceGpm,
+};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 50_000)
+}
+
+fn reference() -> ReferenceGpm {
+    ReferenceGpm::new(ControlChars::default())
+}
+
+fn compare(pieces: &[&str]) -> Option<Divergence> {
+    first_divergence(&mut vm(), &mut reference(), pieces)
+}
+
+fn assert_same(program: &str) {
+    if let Some(d) = compare(&[program]) {
+        panic!("{program:?}\n{d}");
+    }
+}
+
+#[test]
+fn reference_expands_classic_gpm() {
+    let mut r = reference();
+    let out = r.run("§DEF,Suc,<§DEC,§BAR,+,§BIN,~1;,§BIN,1;;;>;§Suc,41; <§a,b;>§DEF,A,<[~1|~2]>;§A,x,§A,y,z;;");
+    assert_eq!(out, "42 §a,b;[x|[y|z]]");
+    assert_eq!(r.end(), "");
+    assert!(r.errors().is_empty());
+}
+
+#[test]
+fn reference_definitions_are_local_to_the_argument_list() {
+    let mut r = reference();
+    let out = r.run("§DEF,Show,<(~1)>;§Show,§DEF,L,<in>;§L;;§VAL,L;after");
+    assert_eq!(out, "(in)after");
+    assert_eq!(r.take_errors()[0].message, "Undefined name L");
+}
+
+#[test]
+fn reference_waits_for_the_rest_of_a_call() {
+    let mut r = reference();
+    assert_eq!(r.run("a§DEF,X,<"), "a");
+    assert_eq!(r.run("x>;<q"), "q");
+    assert_eq!(r.run("uote>§X;"), "uotex");
+    assert_eq!(r.run("§X"), "");
+    assert_eq!(r.end(), "");
+    assert_eq!(r.take_errors()[0].monitor, 11);
+}
+
+#[test]
+fn reference_gives_up_on_endless_recursion() {
+    let mut r = reference();
+    assert_eq!(r.run("§DEF,Loop,<§Loop;>;§Loop;after"), "after");
+    assert_eq!(r.take_errors()[0].monitor, 11);
+}
+
+#[test]
+fn hand_written_programs_agree() {
+    for program in [
+        "§DEF,Suc,<§DEC,§BAR,+,§BIN,~1;,§BIN,1;;;>;§Suc,§Suc,7;;",
+        "§DEF,Sub,<§DEC,§BAR,-,§BIN,~1;,§BIN,~2;;;>;§Sub,3,10;",
+        "§DEF,Div,<§DEC,§BAR,/,§BIN,~1;,§BIN,~2;;;>;§Div,-7,2;|§Div,1,0;|",
+        "§DEC,§BAR,x,§BIN,300;,§BIN,300;;;|§DEC,§BAR,-,§BIN,0;,§BIN,65534;;;",
+        "§DEF,Eq,<§~1,§DEF,~1,<~3>;§DEF,~2,<~2>;,~3,~4;>;",
+        "§DEF,A,<~0:~1>;§A,<§B,~;>,x;",
+        "§DEF,C,<3>;§UPDATE,C,12;§C;§UPDATE,C,1;§C;§VAL,C;",
+        "§DEF,Pair,<(~1)(~2)>;§DEF,Two,<a,b>;§Pair,§Two;;",
+        "§DEF,Semi,<x;y>;§DEF,Show,<[~1]>;§Show,§Semi;;§Semi;",
+        "§DEF,Open,<§Show,z>;§DEF,Show,<[~1]>;§Open;",
+        "§DEF,Show,<[~1]>;§Show,a~1;",
+        "§DEF,Bad,<~>;§Bad;rest",
+        "§DEF,Few,<~2>;§Few,1;rest",
+        "§Nope,§DEF,X,<1>;;§VAL,X;",
+        "§DEF,Show,<[~1]>;§Show,a>b;c",
+        "§BIN,1x;.§DEF,Tiny,<ab>;§UPDATE,Tiny,abc;.",
+        "top>never",
+        "§DEF,Late,<[~1]>;§Late,<unfinished",
+    ] {
+        assert_same(program);
+    }
+}
+
+#[test]
+fn generated_programs_agree() {
+    for seed in 0..300 {
+        let program = ProgramGenerator::new(seed).with_extensions(false).program(12);
+        if let Some(d) = compare(&[&program]) {
+            panic!("seed {seed}: {program:?}\n{d}");
+        }
+    }
+}
+
+#[test]
+fn generated_programs_agree_when_split() {
+    for seed in 0..60 {
+        let program = ProgramGenerator::new(seed).with_extensions(false).program(10);
+        let bounds: Vec<usize> = program.char_indices().map(|(i, _)| i).collect();
+        for k in bounds.iter().step_by(3).skip(1) {
+            let (head, tail) = program.split_at(*k);
+            if let Some(d) = compare(&[head, tail]) {
+                panic!("seed {seed}, split at {k}: {program:?}\n{d}");
+            }
+        }
+    }
+}
+
+// Writes nothing and never raises a monitor
+struct Mute;
+
+impl MacroEngine for Mute {
+    fn run(&mut self, _input: &str) -> String {
+        String::new()
+    }
+
+    fn end(&mut self) -> String {
+        String::new()
+    }
+
+    fn take_errors(&mut self) -> Vec<GpmError> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn first_divergence_points_at_the_first_difference() {
+    let d = first_divergence(&mut reference(), &mut Mute, &["12 <ab", "cd>"]);
+    assert_eq!(
+        d,
+        Some(Divergence::Output {
+            offset: 0,
+            left: "12 abcd".to_string(),
+            right: String::new(),
+        })
+    );
+
+    // the VM's report is not output as far as the comparison goes
+    let d = first_divergence(&mut vm(), &mut Mute, &["§Nope;"]);
+    assert_eq!(
+        d,
+        Some(Divergence::Monitor {
+            index: 0,
+            left: Some((7, "Undefined name Nope".to_string(), 1)),
+            right: None,
+        })
+    );
+    assert_eq!(first_divergence(&mut vm(), &mut Mute, &["§DEF,A,x;"]), None);
+}
+
+#[test]
+fn overflow_wraps_round_in_both_engines() {
+    // 2^31 as the product of two numbers: one past the largest cell
+    let program = "§DEC,§BAR,x,§BIN,32768;,§BIN,65536;;;";
+    assert_eq!(vm().run(program), "-2147483648");
+
+    // ReferenceGpm wraps to the same number, but has no char to carry it in
+    // (a deliberate difference, see reference.rs)
+    assert_eq!(reference().run(program), "65533");
+}
@@ -0,0 +1,78 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn divert_collects_output_for_later_undivert() {
+    let mut vm = vm();
+
+    // table of contents first, although the sections produce the entries
+    let out = vm.run(concat!(
+        "§DEF,Sec,<§DIVERT,1;* ~1\n§DIVERT,2;== ~1 ==\n~2\n§DIVERT;>;",
+        "§Sec,Intro,Hello;§Sec,End,Bye;",
+        "Contents:\n§UNDIVERT,1;\n§UNDIVERT,2;",
+    ));
+    assert_eq!(out, "Contents:\n* Intro\n* End\n\n== Intro ==\nHello\n== End ==\nBye\n");
+    assert_eq!(vm.diversion(1), "");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn host_reads_diversions_after_run() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("a§DIVERT,3;b§DIVERT,-1;lost§DIVERT,7;c"), "a");
+    assert_eq!(vm.run("d"), "");
+    assert_eq!(vm.diversion(3), "b");
+    assert_eq!(vm.diversion(7), "cd");
+    assert_eq!(vm.diversion(-1), "");
+    assert_eq!(vm.take_diversion(7), "cd");
+    assert_eq!(vm.diversion(7), "");
+}
+
+#[test]
+fn undivert_without_argument_flushes_all_in_order() {
+    let mut vm = vm();
+
+    let out = vm.run("§DIVERT,2;two§DIVERT,1;one§DIVERT,0;§UNDIVERT;");
+    assert_eq!(out, "onetwo");
+}
+
+#[test]
+fn undivert_inside_argument_becomes_value() {
+    let mut vm = vm();
+
+    let out = vm.run("§DIVERT,1;xyz§DIVERT;§DEF,T,§UNDIVERT,1;;§T;");
+    assert_eq!(out, "xyz");
+}
+
+#[test]
+fn divert_rejects_non_numbers() {
+    let mut vm = vm();
+
+    let out = vm.run("§DIVERT,x1;");
+    assert!(out.starts_with("\nMONITOR: Non-digit in number \n"), "{:?}", out);
+}
+
+#[test]
+fn divert_rejects_numbers_out_of_range() {
+    let mut vm = vm();
+
+    let out = vm.run("§DIVERT,99999999999;");
+    assert!(out.starts_with("\nMONITOR: Number out of range 99999999999\n"), "{:?}", out);
+    let out = vm.run("§UNDIVERT,-99999999999;");
+    assert!(out.starts_with("\nMONITOR: Number out of range -99999999999\n"), "{:?}", out);
+    // output was not diverted
+    assert_eq!(vm.run("x"), "x");
+}
+
+#[test]
+fn monitor_text_is_not_diverted() {
+    let mut vm = vm();
+
+    let out = vm.run("§DIVERT,1;§DIVERT,one;");
+    assert!(out.starts_with("\nMONITOR: Non-digit in number \n"), "{:?}", out);
+    assert_eq!(vm.diversion(1), "");
+}
@@ -0,0 +1,79 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+const ROW: &str = "§DEF,Row,<~1|~2>;§DEF,BadRow,<§ERROR,<wrong number of fields in ROW>;>;";
+
+#[test]
+fn error_reports_message_and_backtrace() {
+    let mut vm = vm();
+    assert_eq!(vm.run(ROW), "");
+
+    let out = vm.run("§BadRow,a,b,c;");
+    assert_eq!(
+        out,
+        concat!(
+            "\nMONITOR: wrong number of fields in ROW",
+            "\nCurrent macros are ",
+            "\nAlready entered ERROR",
+            "\nArg 1,\twrong number of fields in ROW",
+            "\nAlready entered BadRow",
+            "\nEnd of monitor printingQ",
+        )
+    );
+
+    let errors = vm.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].monitor, 15);
+    assert_eq!(errors[0].message, "wrong number of fields in ROW");
+    assert_eq!(errors[0].report, out);
+    assert!(vm.take_errors().is_empty());
+
+    // the aborted call is gone and reading goes on with the input
+    assert!(vm.run("§BadRow;rest").ends_with("End of monitor printingQrest"));
+    assert_eq!(vm.run("§Row,x,y;"), "x|y");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn try_run_returns_the_error() {
+    let mut vm = vm();
+    assert_eq!(vm.try_run(ROW), Ok(String::new()));
+    assert_eq!(vm.try_run("§Row,a,b;"), Ok("a|b".to_string()));
+
+    let err = vm.try_run("§BadRow,a,b,c;").unwrap_err();
+    assert_eq!(err.monitor, 15);
+    assert_eq!(err.to_string(), "MONITOR 15: wrong number of fields in ROW");
+}
+
+#[test]
+fn built_in_monitors_are_recorded_too() {
+    let mut vm = vm();
+
+    let err = vm.try_run("§DIVERT,x;").unwrap_err();
+    assert_eq!(err.monitor, 10);
+    assert_eq!(err.message, "Non-digit in number");
+    assert!(err.report.ends_with("End of monitor printingQ"));
+
+    // input ending inside a call is reported by end()
+    let _ = vm.run("§Row,a");
+    let out = vm.end();
+    let errors = vm.take_errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[1].monitor, 11);
+    assert_eq!(errors[1].message, "Irremediable error");
+    assert_eq!(errors[1].report, out);
+}
+
+#[test]
+fn warn_records_and_continues() {
+    let mut vm = vm();
+
+    let out = vm.run("a§WARN,<first, with comma>;b§WARN,second;c");
+    assert_eq!(out, "abc");
+    assert_eq!(vm.take_warnings(), ["first, with comma", "second"]);
+    assert!(vm.take_warnings().is_empty());
+    assert!(vm.errors().is_empty());
+}
@@ -0,0 +1,83 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn template_per_item_with_index() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§FOREACH,<[~2:~1]>,a,b,c;"), "[1:a][2:b][3:c]");
+    assert_eq!(vm.run("§FOREACH,<x>;"), "");
+    // an empty item is still an item
+    assert_eq!(vm.run("§FOREACH,<(~1)>,,b;"), "()(b)");
+}
+
+#[test]
+fn items_are_not_rescanned() {
+    let mut vm = vm();
+    let _ = vm.run("§DEF,M,<mac>;");
+
+    // the items are loaded like arguments, so a quoted call stays text,
+    // while calls written in the template itself are evaluated
+    let out = vm.run("§FOREACH,<~1=§M;,>,<§M;>,<<q>>;");
+    assert_eq!(out, "§M;=mac,<q>=mac,");
+}
+
+#[test]
+fn loops_over_the_arguments_of_a_macro() {
+    // quotes as brackets, so that the markup can use < and >
+    let cc = ControlChars {
+        open: '[' as i32,
+        close: ']' as i32,
+        ..ControlChars::default()
+    };
+    let mut html = GpmVm::new(cc, 10_000);
+    let out = html.run("§DEF,Menu,[<ul>§FOREACH,[<li>~1</li>],~1,~2,~3;</ul>];§Menu,Home,About,Blog;");
+    assert_eq!(out, "<ul><li>Home</li><li>About</li><li>Blog</li></ul>");
+
+    let mut vm = vm();
+    assert_eq!(vm.run("§DEF,Enum,<§FOREACH,<~1 = ~2;>,~1,~2;>;§Enum,Red,Green;"), "Red = 1;Green = 2;");
+}
+
+#[test]
+fn nested_foreach_binds_its_own_references() {
+    let mut vm = vm();
+
+    let out = vm.run("§FOREACH,<~1:§FOREACH,<(~2~1)>,x,y;/>,a,b;");
+    assert_eq!(out, "a:(1x)(2y)/b:(1x)(2y)/");
+}
+
+#[test]
+fn inside_an_argument_list() {
+    let mut vm = vm();
+
+    let out = vm.run("§DEF,Wrap,<{~1}~2>;§Wrap,§FOREACH,<~1.>,a,b;,z;");
+    assert_eq!(out, "{a.b.}z");
+}
+
+#[test]
+fn braced_references_are_bound_too() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§FOREACH,<[~{2}:~{1}]>,a,b;"), "[1:a][2:b]");
+}
+
+#[test]
+fn other_references_raise_monitor3() {
+    let mut vm = vm();
+
+    // they would read FOREACH's own frame: its name, the template, ...
+    for template in ["<~0>", "<~3>", "<~{3}>", "<~#>", "<x~>"] {
+        let err = vm.try_run(&format!("§FOREACH,{template},a;")).unwrap_err();
+        assert_eq!(err.monitor, 3, "{template}");
+        assert!(
+            err.report.starts_with("\nMONITOR:\tImpossible argument number in definition of FOREACH"),
+            "{:?}",
+            err.report
+        );
+    }
+    // inside a nested quote they are left for whatever reads them later
+    assert_eq!(vm.run("§FOREACH,<§DEF,M,<~3>;>,a;§M,x,y,z;"), "z");
+}
@@ -0,0 +1,61 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+// Definitions made inside an argument list belong to that call
+const SETUP: &str = "§DEF,Drop,<>;§DEF,Second,<~2>;";
+
+#[test]
+fn survives_the_enclosing_call() {
+    let mut vm = vm();
+    let _ = vm.run(SETUP);
+
+    assert_eq!(vm.run("§Drop,§DEF,L,<l>;§GDEF,G,<g>;;"), "");
+    assert_eq!(vm.run("§DEFINED,L,yes,no;§DEFINED,G,yes,no;§G;"), "noyesg");
+    assert!(vm.errors().is_empty());
+}
+
+#[test]
+fn local_definitions_stay_usable() {
+    let mut vm = vm();
+    let _ = vm.run(SETUP);
+
+    // L was made before GDEF moved the frames up; both resolve afterwards
+    let out = vm.run("§Second,§DEF,L,<l>;,§GDEF,G,<g>;§L;§G;§GDEF,H,<h>;§L;§H;;");
+    assert_eq!(out, "lglh");
+    assert_eq!(vm.run("§DEFINED,L,yes,no;§G;§H;"), "nogh");
+
+    // called from a local macro, whose body moves while it is being read
+    let out = vm.run("§Second,§DEF,L,<§GDEF,Z,<z>;after>;,§L;§L;;/§Z;");
+    assert_eq!(out, "afterafter/z");
+}
+
+#[test]
+fn registers_from_inside_a_macro() {
+    let mut vm = vm();
+    let _ = vm.run(SETUP);
+    let _ = vm.run("§DEF,Reg,<§GDEF,Item~1,~2;>;");
+
+    let out = vm.run("§Drop,§Reg,A,alpha;§Second,x,§Reg,B,beta;;;§ItemA;/§ItemB;");
+    assert_eq!(out, "alpha/beta");
+}
+
+#[test]
+fn local_definition_hides_it_until_the_call_returns() {
+    let mut vm = vm();
+    let _ = vm.run(SETUP);
+
+    let out = vm.run("§Second,§DEF,X,<local>;,§GDEF,X,<global>;§X;;/§X;");
+    assert_eq!(out, "local/global");
+}
+
+#[test]
+fn named_parameters() {
+    let mut vm = vm();
+    let _ = vm.run(SETUP);
+
+    let _ = vm.run("§Drop,§GDEF,Hi,<~<who=world>\nhello ~{who}>;;");
+    assert_eq!(vm.run("§Hi;, §Hi,you;"), "hello world, hello you");
+}
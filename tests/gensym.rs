@@ -0,0 +1,42 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn gensym_yields_unique_symbols() {
+    let mut vm = vm();
+
+    let out = vm.run("§GENSYM,L;,§GENSYM,L;,§GENSYM,tmp_;");
+    assert_eq!(out, "L1,L2,tmp_3");
+    // the counter keeps growing past any fixed width
+    for _ in 0..6 {
+        let _ = vm.run("§GENSYM,x;");
+    }
+    assert_eq!(vm.run("§GENSYM,L;"), "L10");
+    assert_eq!(vm.gensym_counter(), 10);
+}
+
+#[test]
+fn gensym_labels_inside_macros() {
+    let mut vm = vm();
+
+    // the label is generated once per call and bound as an argument
+    let out = vm.run(concat!(
+        "§DEF,Loop,<~1: jmp ~1>;",
+        "§DEF,While,<§Loop,§GENSYM,loop_;;>;",
+        "§While;\n§While;",
+    ));
+    assert_eq!(out, "loop_1: jmp loop_1\nloop_2: jmp loop_2");
+}
+
+#[test]
+fn gensym_counter_can_be_restored() {
+    let mut vm = vm();
+    let _ = vm.run("§GENSYM,a;§GENSYM,a;");
+
+    let mut restored = GpmVm::new(ControlChars::default(), 10_000);
+    restored.set_gensym_counter(vm.gensym_counter());
+    assert_eq!(restored.run("§GENSYM,a;"), "a3");
+}
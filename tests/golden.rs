@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use gpm_in_rust::{run_golden, unified_diff, ControlChars, GoldenOptions, GoldenOutcome};
+
+// Fresh directory tree for one test: (relative path, contents)
+fn tree(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("gpm-golden-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&root);
+    for (path, text) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+    root
+}
+
+fn options() -> GoldenOptions {
+    GoldenOptions::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn compares_every_source_with_its_output() {
+    let root = tree(
+        "compare",
+        &[
+            ("a.gpm", "§DEF,X,<x>;[§X;]\n"),
+            ("a.out", "[x]\n"),
+            ("sub/b.gpm", "§DEF,Y,<~1~1>;§Y,ab;\n"),
+            ("sub/b.out", "abab\nextra\n"),
+            ("c.gpm", "no expected output"),
+            ("notes.txt", "not a source"),
+        ],
+    );
+
+    let report = run_golden(&root, &options()).unwrap();
+    let outcomes: Vec<_> = report
+        .cases
+        .iter()
+        .map(|c| (c.source.strip_prefix(&root).unwrap().to_path_buf(), &c.outcome))
+        .collect();
+    assert_eq!(outcomes.len(), 3);
+    assert_eq!(outcomes[0], (PathBuf::from("a.gpm"), &GoldenOutcome::Passed));
+    assert_eq!(outcomes[1], (PathBuf::from("c.gpm"), &GoldenOutcome::Missing));
+    assert_eq!(outcomes[2].0, PathBuf::from("sub/b.gpm"));
+    match outcomes[2].1 {
+        GoldenOutcome::Failed(diff) => assert!(diff.ends_with("@@ -1,2 +1,1 @@\n abab\n-extra\n"), "{diff}"),
+        other => panic!("{other:?}"),
+    }
+    assert!(!report.passed());
+}
+
+#[test]
+fn prelude_and_includes() {
+    let root = tree(
+        "prelude",
+        &[
+            ("lib/defs.gpm", "§DEF,Hi,<hello ~1>;\n"),
+            ("t.gpm", "§INCLUDE,lib/defs.gpm;§Hi,§Name;;"),
+            ("t.out", "\nhello world"),
+        ],
+    );
+    let prelude = root.join("prelude.txt");
+    fs::write(&prelude, "§DEF,Name,<world>;\n").unwrap();
+
+    // lib/defs.gpm is a source too, with an empty expected output
+    fs::write(root.join("lib/defs.out"), "\n").unwrap();
+    let report = run_golden(&root, &options().with_prelude(&prelude)).unwrap();
+    assert!(report.passed(), "{:?}", report.cases);
+}
+
+#[test]
+fn update_writes_the_expected_files() {
+    let root = tree("update", &[("u.gpm", "§DEF,X,<new>;§X;"), ("u.out", "old")]);
+
+    let report = run_golden(&root, &options().with_update(true)).unwrap();
+    assert_eq!(report.cases[0].outcome, GoldenOutcome::Updated);
+    assert_eq!(fs::read_to_string(root.join("u.out")).unwrap(), "new");
+    assert!(run_golden(&root, &options()).unwrap().passed());
+}
+
+#[test]
+fn unified_diff_format() {
+    assert_eq!(unified_diff("a\nb\n", "a\nb\n", "x", "y"), "");
+
+    let lines = |changed: &[(usize, &str)]| -> String {
+        (1..=12)
+            .map(|n| match changed.iter().find(|(k, _)| *k == n) {
+                Some((_, text)) => format!("{text}\n"),
+                None => format!("{n}\n"),
+            })
+            .collect()
+    };
+    let old = lines(&[]);
+    let new = lines(&[(2, "two"), (11, "eleven")]);
+    assert_eq!(
+        unified_diff(&old, &new, "old", "new"),
+        "--- old\n+++ new\n\
+         @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+         @@ -8,5 +8,5 @@\n 8\n 9\n 10\n-11\n+eleven\n 12\n"
+    );
+
+    // changes up to six lines apart share a hunk
+    let near = lines(&[(2, "two"), (9, "nine")]);
+    assert!(unified_diff(&old, &near, "o", "n").contains("@@ -1,12 +1,12 @@\n"));
+
+    assert_eq!(
+        unified_diff("a\n", "a", "o", "n"),
+        "--- o\n+++ n\n@@ -1,1 +1,1 @@\n-a\n+a\n\\ No newline at end of file\n"
+    );
+    assert_eq!(unified_diff("", "x\n", "o", "n"), "--- o\n+++ n\n@@ -0,0 +1,1 @@\n+x\n");
+}
+
+#[test]
+fn cli_subcommand() {
+    let root = tree("cli", &[("a.gpm", "§DEF,X,<x>;§X;"), ("a.out", "y")]);
+    let bin = env!("CARGO_BIN_EXE_gpm-in-rust");
+
+    let out = Command::new(bin).arg("golden").arg(&root).output().unwrap();
+    assert!(!out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.starts_with("BŁĄD     "), "{stdout}");
+    assert!(stdout.contains("@@ -1,1 +1,1 @@\n-y\n\\ No newline at end of file\n+x\n"), "{stdout}");
+
+    let out = Command::new(bin).args(["golden", "--update"]).arg(&root).output().unwrap();
+    assert!(out.status.success());
+    let out = Command::new(bin).arg("golden").arg(&root).args(["--mem", "5000"]).output().unwrap();
+    assert!(out.status.success());
+
+    assert!(!Command::new(bin).output().unwrap().status.success());
+}
+
+#[test]
+fn cli_control_characters() {
+    let root = tree("cli_cc", &[("a.gpm", "$DEF|X|[<x>];$X; # uwaga\n"), ("a.out", "<x> ")]);
+    let bin = env!("CARGO_BIN_EXE_gpm-in-rust");
+    let golden = |flags: &[&str]| Command::new(bin).arg("golden").arg(&root).args(flags).output().unwrap();
+
+    let flags = ["--open", "[", "--close", "]", "--def", "$", "--arg-sep", "|", "--comment", "#"];
+    let out = golden(&flags);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stdout));
+    assert!(String::from_utf8(out.stdout).unwrap().starts_with("ok       "));
+
+    // not one character, or one already used by another role
+    assert!(!golden(&["--open", "[["]).status.success());
+    assert!(!golden(&["--open", ","]).status.success());
+    assert!(!golden(&["--apply"]).status.success());
+}
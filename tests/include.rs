@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::PathBuf;
+
+use gpm_in_rust::{ControlChars, FileResolver, GpmVm, IncludeError, SandboxResolver};
+
+// Fresh directory tree for one test: (relative path, contents)
+fn tree(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("gpm-include-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&root);
+    for (path, text) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+    root
+}
+
+fn vm_with(root: &PathBuf) -> GpmVm {
+    let mut vm = GpmVm::new(ControlChars::default(), 10_000);
+    vm.set_file_resolver(SandboxResolver::new(root).with_search_path(["lib"]));
+    vm
+}
+
+#[test]
+fn include_pushes_file_text_at_the_call() {
+    let root = tree(
+        "basic",
+        &[
+            ("defs.gpm", "§DEF,Hello,<Hello, ~1!>;"),
+            ("lib/row.gpm", "<~1|~2>"),
+        ],
+    );
+    let mut vm = vm_with(&root);
+
+    assert_eq!(vm.run("[§INCLUDE,defs.gpm;]"), "[]");
+    assert_eq!(vm.run("§Hello,world;"), "Hello, world!");
+    // found through the search path; included text is scanned, so the quotes go
+    assert_eq!(vm.run("(§INCLUDE,row.gpm;)"), "(~1|~2)");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn include_outside_root_raises_monitor12() {
+    let root = tree("escape", &[("a.gpm", "a")]);
+    let mut vm = vm_with(&root);
+
+    let out = vm.run("§INCLUDE,lib/../../secret;");
+    assert!(
+        out.starts_with("\nMONITOR: Include path outside root lib/../../secret\n"),
+        "{:?}",
+        out
+    );
+    let out = vm.run("§INCLUDE,/etc/hostname;");
+    assert!(out.starts_with("\nMONITOR: Include path outside root /etc/hostname\n"), "{:?}", out);
+}
+
+#[test]
+fn include_cycle_raises_monitor13() {
+    let root = tree(
+        "cycle",
+        &[("a.gpm", "A§INCLUDE,b.gpm;"), ("b.gpm", "B§INCLUDE,./a.gpm;")],
+    );
+    let mut vm = vm_with(&root);
+
+    let out = vm.run("§INCLUDE,a.gpm;");
+    assert!(out.starts_with("AB\nMONITOR: Recursive include of ./a.gpm\n"), "{:?}", out);
+
+    // once a file has been read completely it may be included again
+    let root = tree("twice", &[("x.gpm", "x")]);
+    let mut vm = vm_with(&root);
+    assert_eq!(vm.run("§INCLUDE,x.gpm;§INCLUDE,x.gpm;"), "xx");
+}
+
+#[test]
+fn missing_file_or_resolver_raises_monitor14() {
+    let root = tree("missing", &[]);
+    let mut vm = vm_with(&root);
+    assert!(vm.run("§INCLUDE,nope.gpm;").starts_with("\nMONITOR: Cannot include nope.gpm\n"));
+
+    let mut vm = GpmVm::new(ControlChars::default(), 10_000);
+    assert!(vm.run("§INCLUDE,nope.gpm;").starts_with("\nMONITOR: Cannot include nope.gpm\n"));
+}
+
+#[test]
+fn sandbox_resolver_names_files_canonically() {
+    let root = tree("names", &[("lib/x.gpm", "x")]);
+    let mut r = SandboxResolver::new(&root).with_search_path(["lib"]);
+
+    let a = r.resolve("x.gpm").unwrap();
+    let b = r.resolve("./lib/../lib/x.gpm").unwrap();
+    assert_eq!(a.name, b.name);
+    assert_eq!(a.text, "x");
+    assert_eq!(r.resolve("../../x.gpm").err(), Some(IncludeError::OutsideRoot));
+}
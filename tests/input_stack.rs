@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use gpm_in_rust::{ControlChars, FileResolver, GpmVm, IncludeError, IncludeFile};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+// Files from a table, no file system involved
+struct Files(HashMap<&'static str, &'static str>);
+
+impl FileResolver for Files {
+    fn resolve(&mut self, path: &str) -> Result<IncludeFile, IncludeError> {
+        let text = self.0.get(path).ok_or(IncludeError::NotFound)?;
+        Ok(IncludeFile {
+            name: path.to_string(),
+            text: text.to_string(),
+        })
+    }
+}
+
+fn vm_with(files: &[(&'static str, &'static str)]) -> GpmVm {
+    let mut vm = vm();
+    vm.set_file_resolver(Files(files.iter().copied().collect()));
+    vm
+}
+
+#[test]
+fn lines_of_the_input_are_counted_across_chunks() {
+    let mut vm = vm();
+    assert_eq!(vm.input_position(), ("input", 1));
+    let _ = vm.run("a\nb");
+    assert_eq!(vm.input_position(), ("input", 2));
+    let _ = vm.run("\n§DEF,A,<x\ny>;\n");
+    assert_eq!(vm.input_position(), ("input", 5));
+    // newlines that come out of macro bodies are not input lines
+    let _ = vm.run("§A;§A;");
+    assert_eq!(vm.input_position(), ("input", 5));
+}
+
+#[test]
+fn monitors_record_the_file_and_line() {
+    let mut vm = vm_with(&[("defs.gpm", "§DEF,A,<a>;\n§DEF,B,<b>;\n\n§Nope;\n")]);
+    let _ = vm.run("1\n§INCLUDE,defs.gpm;\n§Nope;");
+    let errors = vm.take_errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!((errors[0].source.as_str(), errors[0].line), ("defs.gpm", 4));
+    // back in the input after the file, which did not count
+    assert_eq!((errors[1].source.as_str(), errors[1].line), ("input", 3));
+    assert_eq!(vm.input_position(), ("input", 3));
+}
+
+#[test]
+fn pushed_text_is_read_before_the_next_input() {
+    let mut vm = vm();
+    vm.push_input("host", "§DEF,Greet,<hello ~1>;");
+    vm.push_input("first", "[");
+    assert_eq!(vm.run("§Greet,you;]"), "[hello you]");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn a_call_may_span_pushed_text_and_input() {
+    let mut vm = vm();
+    vm.push_input("host", "§DEF,Pair,<~1~2>;§Pair,a");
+    assert_eq!(vm.run(",b;"), "ab");
+}
+
+#[test]
+fn includes_nest_and_return_to_the_including_file() {
+    let mut vm = vm_with(&[
+        ("outer.gpm", "(§INCLUDE,inner.gpm;)\nafter"),
+        ("inner.gpm", "in\nner"),
+    ]);
+    assert_eq!(vm.run("§INCLUDE,outer.gpm;."), "(in\nner)\nafter.");
+    assert!(vm.errors().is_empty());
+
+    // only files still being read count as recursive
+    assert_eq!(vm.run("§INCLUDE,inner.gpm;"), "in\nner");
+    assert!(vm.errors().is_empty());
+}
+
+#[test]
+fn unmatched_close_drops_pushed_text_too() {
+    let mut vm = vm();
+    vm.push_input("host", "x>lost");
+    assert_eq!(vm.run("also lost"), "x");
+    assert_eq!(vm.run("y"), "y");
+}
+
+#[test]
+fn pushed_names_do_not_count_as_includes() {
+    let mut vm = vm_with(&[("x.gpm", "from the file")]);
+    vm.push_input("x.gpm", "§INCLUDE,x.gpm;/");
+    assert_eq!(vm.run(""), "from the file/");
+    assert!(vm.errors().is_empty());
+}
+
+#[test]
+fn end_drops_pushed_text_not_yet_read() {
+    let mut vm = vm();
+    vm.push_input("host", "§DEF,Late,<late>;never");
+    assert_eq!(vm.end(), "");
+    assert!(vm.errors().is_empty());
+    assert_eq!(vm.run("next§DEFINED,Late,y,n;"), "nextn");
+}
@@ -0,0 +1,63 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    let mut vm = GpmVm::new(ControlChars::default(), 10_000);
+    vm.set_step_checks(true);
+    vm
+}
+
+// Programs that exercise every way the store changes shape: nested calls,
+// definitions in arguments, machine macros that grow or move frames, and
+// monitors that abandon calls half way.
+const PROGRAMS: &[&str] = &[
+    "§DEF,Suc,<§1,2,3,4,5,6,7,8,9,10,§DEF,1,<~>~1;;>;§Suc,3;§Suc,§Suc,7;;",
+    "§DEF,Id,<~1>;§Id,a§DEF,L,<l>;b;§Id,§Id,§Id,x;;;",
+    "§DEF,C,<0>;§UPDATE,C,1;§VAL,C;§BIN,-12;§DEC,42;§BAR,+,2,3;",
+    "§DEF,Link,<~<url, title=Home>\n[~{title}](~{url})>;§Link,a;§Link,b,B;",
+    "§FOREACH,<(~2~1§FOREACH,<.~1>,x,y;)>,a,b;",
+    "§DEF,Drop,<>;§Drop,§DEF,L,<l>;§GDEF,G,<g>;§L;;§G;",
+    "§DEF,Drop,<>;§Drop,§DEF,L,<l>;§DEF,M,<m>;§GDEF,G,<g>;§M;§L;;§G;",
+    "§DEF,A,<a>;§DEF,B,<b>;§DEF,C,<c>;§UNDEF,A;§C;§B;",
+    "§DEF,T,<~1>;§UNDEF,T;§DEFINED,T,y,n;§GENSYM,g;",
+    "§UPPER,abc;§INDEX,hello,l;§REPLACE,aXbX,X,-;§TRANSLIT,abc,ab,xy;",
+    "§DIVERT,1;hidden§DIVERT;shown",
+    "§DEF,X,<a;b>;§DEF,Y,<[~1]>;§Y,§X;;§Y,a~1;",
+    "§DEF,X,<§Y,a>;§X;§Nope,a;§Y,a>b;",
+    "§DEF,R,<~{12}~#>;§R,1,2,3,4,5,6,7,8,9,10,11,12;§R;",
+    // a name finished inside the body of a call begun in it
+    "§DEF,M,<~1,>;§DEF,c,<[~1]>;§§M,c;x;§§M,;x;",
+    // Monitor4 inside an argument list, on its way to Monitor11
+    "§DEF,A,<~2>;§DEF,B,<(~1)>;§B,§A,x;;after",
+];
+
+#[test]
+fn every_step_keeps_the_store_consistent() {
+    for program in PROGRAMS {
+        let mut vm = vm();
+        let _ = vm.run(program);
+        assert_eq!(vm.check_invariants(), Ok(()), "{program:?}");
+    }
+}
+
+#[test]
+fn also_when_fed_in_chunks() {
+    for program in PROGRAMS {
+        let mut vm = vm();
+        for ch in program.chars() {
+            let _ = vm.run(&ch.to_string());
+            assert_eq!(vm.check_invariants(), Ok(()), "{program:?}");
+        }
+        let _ = vm.end();
+        assert_eq!(vm.check_invariants(), Ok(()), "{program:?}");
+    }
+}
+
+#[test]
+fn holds_with_input_left_inside_a_call() {
+    let mut vm = vm();
+
+    let _ = vm.run("§DEF,Y,<[~1]>;§Y,§Y,a,<b");
+    assert_eq!(vm.check_invariants(), Ok(()));
+    let _ = vm.end();
+    assert_eq!(vm.check_invariants(), Ok(()));
+}
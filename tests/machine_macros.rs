@@ -0,0 +1,43 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn bin_result_survives_the_call() {
+    let mut vm = vm();
+
+    // BIN leaves its number in the cell at the old S, where the caller's
+    // argument (here DEC's) picks it up
+    assert_eq!(vm.run("§DEC,§BIN,42;;"), "42");
+    assert_eq!(vm.run("§DEC,§BIN,-7;;"), "-7");
+    assert_eq!(vm.run("§DEC,§BIN,+0;;"), "0");
+    assert_eq!(vm.run("§DEC,§BAR,x,§BIN,6;,§BIN,7;;;"), "42");
+}
+
+#[test]
+fn numbers_wrap_round_a_cell() {
+    let mut vm = vm();
+
+    // 2^32 + 1 and -(2^31), made by BIN and by BAR
+    assert_eq!(vm.run("§DEC,§BIN,4294967297;;"), "1");
+    assert_eq!(vm.run("§DEC,§BIN,-2147483648;;"), "-2147483648");
+    assert_eq!(vm.run("§DEC,§BAR,-,§BIN,-2147483648;,§BIN,1;;;"), "2147483647");
+    assert_eq!(vm.run("§DEC,§BAR,/,§BIN,-2147483648;,§BIN,-1;;;"), "-2147483648");
+}
+
+#[test]
+fn smallest_store_holds_just_the_machine_macros() {
+    let mut vm = GpmVm::new(ControlChars::default(), GpmVm::min_mem_size());
+
+    // no room for anything else, but no panic either
+    let _ = vm.run("§DEF,A,<a>;§A;");
+    let _ = vm.end();
+}
+
+#[test]
+#[should_panic(expected = "cannot hold the machine macros")]
+fn a_smaller_store_is_refused() {
+    let _ = GpmVm::new(ControlChars::default(), GpmVm::min_mem_size() - 1);
+}
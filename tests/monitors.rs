@@ -0,0 +1,164 @@
+// One case per Appendix 2 monitor: the exact diagnostic text, and what the VM
+// does afterwards. Monitor6 does not exist.
+
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+const SHOW: &str = "§DEF,Show,<[~1]>;";
+
+// Runs src, expecting exactly one report from monitor nr, and checks that the
+// VM still expands a plain call afterwards.
+fn run_monitor(src: &str, nr: u8) -> String {
+    let mut vm = vm();
+    let _ = vm.run(SHOW);
+    let out = vm.run(src);
+
+    let errors = vm.take_errors();
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert_eq!(errors[0].monitor, nr);
+    assert!(out.contains(&errors[0].report), "{out:?}");
+
+    assert_eq!(vm.run("§Show,ok;"), "[ok]");
+    assert!(vm.errors().is_empty());
+    out
+}
+
+#[test]
+fn monitor1_unmatched_semicolon_is_copied() {
+    let out = run_monitor("§DEF,X,<a;b>;§Show,§X;;after", 1);
+    assert_eq!(
+        out,
+        "\nMONITOR: Unmatched semicolon in definition of X\
+         \nIf this had been quoted the result would be \n[a;b]after"
+    );
+}
+
+#[test]
+fn monitor2_unquoted_tilde_is_copied() {
+    let out = run_monitor("§Show,a~1;after", 2);
+    assert_eq!(
+        out,
+        "\nMONITOR: Unquoted tilde in argument list of Show\
+         \nIf this had been quoted the result would be \n[a~1]after"
+    );
+    // outside an argument list a ~ in the input is just text
+    assert_eq!(vm().run("a~1"), "a~1");
+}
+
+#[test]
+fn monitor3_impossible_argument_number() {
+    let out = run_monitor("§DEF,X,<~!>;§X;after", 3);
+    assert_eq!(
+        out,
+        "\nMONITOR:\tImpossible argument number in definition of X\
+         \nCurrent macros are \nAlready entered X\nEnd of monitor printingQafter"
+    );
+}
+
+#[test]
+fn monitor4_missing_argument() {
+    let out = run_monitor("§DEF,X,<~2>;§X,a;after", 4);
+    assert_eq!(
+        out,
+        "\nMONITOR: No argument 2\n in call for X\
+         \nCurrent macros are \nAlready entered X\nArg 1,\ta\nEnd of monitor printingQafter"
+    );
+}
+
+#[test]
+fn monitor5_final_semicolon_is_added() {
+    // X's body ends inside Show's argument list: C steps back onto the
+    // Marker, Show is applied, and the Marker then ends X as usual
+    let out = run_monitor("§DEF,X,<§Show,a>;§X;after", 5);
+    assert_eq!(
+        out,
+        "\nMONITOR: Terminator in argument list for Show\
+         \nProbably due to a semicolon missing from the definition of X\
+         \nIf a final semicolon is added the result is \n[a]after"
+    );
+}
+
+#[test]
+fn monitor7_undefined_name() {
+    let out = run_monitor("§Nope,a;after", 7);
+    assert_eq!(
+        out,
+        "\nMONITOR: Undefined name Nope\
+         \nCurrent macros are \nAlready entered Nope\nArg 1,\ta\nEnd of monitor printingQafter"
+    );
+
+    // also from a macro body, with the calling macro in the trace
+    let out = run_monitor("§DEF,X,<§Nope;>;§X,b;after", 7);
+    assert_eq!(
+        out,
+        "\nMONITOR: Undefined name Nope\
+         \nCurrent macros are \nAlready entered Nope\nAlready entered X\nEnd of monitor printingQafter"
+    );
+}
+
+#[test]
+fn monitor8_unmatched_close_quote() {
+    // the call is abandoned and the rest of the input is read as text; the
+    // final Q is loaded into Show's argument (H is not 0) and goes with it
+    let out = run_monitor("§Show,a>b;after", 8);
+    assert_eq!(
+        out,
+        "\nMONITOR: Unmatched >. Probably machine error. \
+         \nCurrent macros are \nNot yet entered Show\nArg 1,\ta...\t(Incomplete)\
+         \nEnd of monitor printingb;after"
+    );
+}
+
+#[test]
+fn monitor9_update_too_long() {
+    let out = run_monitor("§DEF,X,<ab>;§UPDATE,X,abc;after§X;", 9);
+    assert_eq!(
+        out,
+        "\nMONITOR: Update argument too long for X\
+         \nCurrent macros are \nAlready entered UPDATE\nArg 1,\tX\nArg 2,\tabc\
+         \nEnd of monitor printingQafterab"
+    );
+}
+
+#[test]
+fn monitor10_non_digit_in_bin() {
+    let out = run_monitor("§BIN,1x;after", 10);
+    assert_eq!(
+        out,
+        "\nMONITOR: Non-digit in number \
+         \nCurrent macros are \nAlready entered BIN\nArg 1,\t1x\nEnd of monitor printingQafter"
+    );
+}
+
+#[test]
+fn monitor11_input_ends_inside_a_call() {
+    let mut vm = vm();
+    let _ = vm.run(SHOW);
+
+    assert_eq!(vm.run("§Show,§Show,abc"), "");
+    // only the innermost call lists its arguments
+    let out = vm.end();
+    assert_eq!(
+        out,
+        "\nCurrent macros are \nNot yet entered Show\nArg 1,\tabc...\t(Incomplete)\
+         \nNot yet entered Show\nEnd of monitor printingQ"
+    );
+    let errors = vm.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].monitor, 11);
+    assert_eq!(errors[0].message, "Irremediable error");
+
+    assert_eq!(vm.run("§Show,ok;"), "[ok]");
+}
+
+#[test]
+fn definitions_made_before_an_error_are_kept() {
+    let mut vm = vm();
+
+    let _ = vm.run("§DEF,A,<a>;§Nope;§DEF,B,<b>;");
+    assert_eq!(vm.take_errors().len(), 1);
+    assert_eq!(vm.run("§A;§B;"), "ab");
+}
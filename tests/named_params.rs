@@ -0,0 +1,101 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+const LINK: &str = "§DEF,Link,<~<url, title>\n[~{title}](~{url})>;";
+
+#[test]
+fn names_resolve_to_positions() {
+    let mut vm = vm();
+    assert_eq!(vm.run(LINK), "");
+
+    assert_eq!(vm.run("§Link,x.html,Home;"), "[Home](x.html)");
+    // positional references keep working next to the names
+    assert_eq!(vm.run("§DEF,P,<~<a,b>\n~{b}~1~2~0>;§P,x,y;"), "yxyP");
+    // a one-letter name at position ten makes the body longer
+    let out = vm.run("§DEF,T,<~<a,b,c,d,e,f,g,h,i,j>\n~{j}~{a}>;§T,1,2,3,4,5,6,7,8,9,10;");
+    assert_eq!(out, "101");
+}
+
+#[test]
+fn names_are_resolved_when_defined() {
+    let mut vm = vm();
+    let _ = vm.run(LINK);
+
+    // the stored value holds positions; the header line is not text any more
+    assert_eq!(vm.run("§VAL,Link;"), "[~{2}](~{1})");
+}
+
+#[test]
+fn update_replaces_the_body_only() {
+    let mut vm = vm();
+    let _ = vm.run(LINK);
+
+    assert_eq!(vm.run("§UPDATE,Link,<~2 at ~1>;§Link,x.html,Home;"), "Home at x.html");
+    assert_eq!(vm.run("§VAL,Link;"), "~2 at ~1");
+}
+
+#[test]
+fn header_uses_the_configured_control_characters() {
+    let cc = ControlChars {
+        open: '[' as i32,
+        close: ']' as i32,
+        arg_sep: '|' as i32,
+        load_arg: '#' as i32,
+        ..ControlChars::default()
+    };
+    let mut vm = GpmVm::new(cc, 10_000);
+    assert_eq!(vm.run("§DEF|Pair|[#[a|b=2]\n#{b}#{a}];§Pair|1;§Pair|1|x;"), "21x1");
+    // a header in the default characters is just text here
+    assert_eq!(vm.run("§DEF|Plain|[#(a)\n];§VAL|Plain;"), "#(a)\n");
+}
+
+#[test]
+fn nested_definitions_keep_their_own_names() {
+    let mut vm = vm();
+
+    // ~{y} is one quote level down, so Outer leaves it for Inner to resolve
+    let out = vm.run("§DEF,Outer,<~<x>\n§DEF,Inner,<~<y>\n(~{y})>;§Inner,~{x};>;§Outer,v;");
+    assert_eq!(out, "(v)");
+}
+
+#[test]
+fn definitions_inside_arguments() {
+    // a DEF in an argument list leaves its entry in the argument text; the
+    // shortened value must leave that text and the rest of the call intact
+    let named = vm().run("§DEF,Id,<~2~1>;§Id,§DEF,L,<~<n>\n-~{n}->;§L,b;,a;");
+    let plain = vm().run("§DEF,Id,<~2~1>;§Id,§DEF,L,<~<n>\n-~{1}->;§L,b;,a;");
+    assert!(named.starts_with("a"), "{named:?}");
+    assert!(named.ends_with("-b-"), "{named:?}");
+    assert_eq!(named, plain);
+}
+
+#[test]
+fn unknown_names_fail_at_call_time() {
+    let mut vm = vm();
+    let _ = vm.run("§DEF,U,<~<a>\n~{b}>;");
+
+    let err = vm.try_run("§U,x;").unwrap_err();
+    assert_eq!(err.monitor, 3);
+}
+
+#[test]
+fn bad_parameter_list_raises_monitor17() {
+    let mut vm = vm();
+
+    for def in ["§DEF,B,<~<a,a>\nx>;", "§DEF,B,<~<a b>\nx>;", "§DEF,B,<~<1a>\nx>;", "§DEF,B,<~<a> x>;"] {
+        let err = vm.try_run(def).unwrap_err();
+        assert_eq!(err.monitor, 17, "{def}");
+        assert!(
+            err.report.starts_with("\nMONITOR: Impossible parameter list in definition of B\n"),
+            "{:?}",
+            err.report
+        );
+    }
+    // nothing was defined
+    assert_eq!(vm.run("§DEFINED,B,yes,no;"), "no");
+    // an empty list declares no names
+    assert_eq!(vm.run("§DEF,Z,<~<>\nz>;§Z;"), "z");
+}
@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+// The library without the `std` feature is #![no_std]: building it for the
+// host proves that nothing outside core and alloc is used. A target directory
+// of its own keeps it clear of the build running this test.
+#[test]
+fn library_builds_without_std() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = root.join("target").join("no_std");
+    let output = Command::new(env!("CARGO"))
+        .current_dir(&root)
+        .args(["build", "--lib", "--no-default-features", "--offline"])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,116 @@
+use gpm_in_rust::{prelude_for, Cell, ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::with_prelude(ControlChars::default(), 20_000).unwrap()
+}
+
+#[test]
+fn loads_quietly() {
+    let mut vm = vm();
+    assert!(vm.errors().is_empty());
+    assert_eq!(vm.run("text"), "text");
+}
+
+#[test]
+fn successor() {
+    let mut vm = vm();
+
+    let out: Vec<String> = (0..10).map(|d| vm.run(&format!("§Suc,{d};"))).collect();
+    assert_eq!(out, ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+}
+
+#[test]
+fn conditional_by_name_lookup() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§Eq,abc,abc,same,different;"), "same");
+    assert_eq!(vm.run("§Eq,abc,abd,same,different;"), "different");
+    // the branches are text; selecting a macro name evaluates one
+    assert_eq!(vm.run("§Eq,1,1,<§Suc,1;>,x;"), "§Suc,1;");
+    assert_eq!(vm.run("§DEF,T,<then>;§DEF,F,<else>;§§Eq,1,2,T,F;;"), "else");
+    // the trial definitions do not outlive the call
+    assert_eq!(vm.run("§DEFINED,abc,y,n;"), "n");
+}
+
+#[test]
+fn machine_arithmetic() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§Add,40,2;/§Sub,3,10;/§Mul,-6,7;"), "42/-7/-42");
+    assert_eq!(vm.run("§Add,§Add,1,2;,§Mul,3,4;;"), "15");
+    assert_eq!(vm.run("§DEC,§BIN,-0012;;/§DEC,§BIN,+5;;"), "-12/5");
+}
+
+#[test]
+fn counters_via_update() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§NewCounter,Hits;§Hits;"), "0");
+    let out: Vec<String> = (0..12).map(|_| vm.run("§Next,Hits;")).collect();
+    assert_eq!(out.last().unwrap(), "12");
+    assert_eq!(vm.run("§Hits;"), "12");
+    // a second counter is independent
+    assert_eq!(vm.run("§NewCounter,Other;§Next,Other;/§Hits;"), "1/12");
+}
+
+#[test]
+fn recursive_factorial() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§Fact,0;/§Fact,1;/§Fact,5;/§Fact,10;"), "1/1/120/3628800");
+    assert!(vm.errors().is_empty());
+}
+
+#[test]
+fn follows_the_configured_control_characters() {
+    let cc = ControlChars {
+        def: '&' as Cell,
+        open: '[' as Cell,
+        close: ']' as Cell,
+        load_arg: '$' as Cell,
+        ..ControlChars::default()
+    };
+    let mut vm = GpmVm::with_prelude(cc, 20_000).unwrap();
+
+    assert!(vm.errors().is_empty());
+    assert_eq!(vm.run("&Suc,4;/&Fact,4;/&Eq,x,x,[<y>],n;"), "5/24/<y>");
+}
+
+#[test]
+fn leaves_out_definitions_holding_the_comment_character() {
+    let cc = ControlChars {
+        comment: Some('-' as Cell),
+        ..ControlChars::default()
+    };
+    let mut vm = GpmVm::with_prelude(cc, 20_000).unwrap();
+
+    assert!(vm.errors().is_empty());
+    assert_eq!(vm.run("§Add,2,3;/§Mul,2,3;/§Suc,4;"), "5/6/5");
+    // Sub would have been cut at its '-'; Fact is still defined, but needs Sub
+    assert_eq!(vm.try_run("§Sub,5,3;").unwrap_err().monitor, 7);
+    let (text, omitted) = prelude_for(cc);
+    assert!(!text.contains('-'));
+    assert_eq!(omitted, ["Sub"]);
+
+    // a newline comment character: the definitions are simply run together
+    let cc = ControlChars {
+        comment: Some('\n' as Cell),
+        ..ControlChars::default()
+    };
+    let mut vm = GpmVm::with_prelude(cc, 20_000).unwrap();
+    assert!(vm.errors().is_empty());
+    assert_eq!(vm.run("§Sub,5,3;/§Fact,4;"), "2/24");
+    assert!(prelude_for(cc).1.is_empty());
+
+    let cc = ControlChars {
+        comment: Some('x' as Cell),
+        ..ControlChars::default()
+    };
+    assert_eq!(prelude_for(cc).1, ["Mul", "Next"]);
+}
+
+#[test]
+fn a_store_too_small_for_the_prelude_is_an_error() {
+    let err = GpmVm::with_prelude(ControlChars::default(), GpmVm::min_mem_size() + 100).err();
+    assert_eq!(err.map(|e| e.monitor), Some(11));
+}
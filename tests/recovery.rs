@@ -0,0 +1,40 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+// Recovery after Monitor11. Appendix 2 goes on reading where it was; this VM
+// first drops the calls in progress (see abandon_calls).
+
+#[test]
+fn an_error_in_a_top_level_call_recovers_as_in_appendix_2() {
+    // reading goes on with the input either way; Appendix 2 would only leave
+    // BIN's frame on the stack
+    let out = vm().run("§BIN,1x;after");
+    assert!(out.ends_with("\nAlready entered BIN\nArg 1,\t1x\nEnd of monitor printingQafter"), "{out:?}");
+}
+
+#[test]
+fn an_error_in_a_body_abandons_the_rest_of_it() {
+    // Appendix 2 would read on through "tail" and past the end of the body
+    let out = vm().run("§DEF,X,<~2tail>;§X,a;after");
+    assert!(!out.contains("tail"), "{out:?}");
+    assert!(out.ends_with("\nEnd of monitor printingQafter"), "{out:?}");
+}
+
+#[test]
+fn an_error_in_an_argument_list_abandons_the_call() {
+    // Appendix 2 would load Q and the rest into Show's argument; here Q goes
+    // with the abandoned argument and the rest is read at the top level
+    let out = vm().run("§Show,a§BIN,x;b;after");
+    assert!(out.ends_with("\nNot yet entered Show\nEnd of monitor printingb;after"), "{out:?}");
+}
+
+#[test]
+fn definitions_made_inside_abandoned_calls_are_dropped() {
+    let mut vm = vm();
+
+    let _ = vm.run("§DEF,K,<k>;§DEF,X,<§DEF,L,<l>;~2>;§X;");
+    assert_eq!(vm.run("§DEFINED,L,yes,no;§DEFINED,K,yes,no;"), "noyes");
+}
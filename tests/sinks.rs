@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use gpm_in_rust::{ControlChars, FnSink, GpmVm, IoSink};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn string_sink_collects_what_run_would_return() {
+    let mut vm = vm();
+    vm.set_output_sink(String::new());
+    assert_eq!(vm.run("§DEF,A,<[~1]>;§A,x;"), "");
+    assert_eq!(vm.run("§A,§A,y;;"), "");
+    assert_eq!(vm.end(), "");
+    assert_eq!(vm.take_output_sink::<String>().as_deref(), Some("[x][[y]]"));
+
+    // without the sink output is returned again
+    assert_eq!(vm.run("§A,z;"), "[z]");
+}
+
+#[test]
+fn closure_sees_characters_as_they_are_produced() {
+    let seen = Rc::new(RefCell::new(String::new()));
+    let mut vm = vm();
+    let log = Rc::clone(&seen);
+    vm.set_output_sink(FnSink(move |c| log.borrow_mut().push(c)));
+
+    // the first line is out before the call after it is complete
+    let _ = vm.run("first\n§DEF,A,<");
+    assert_eq!(*seen.borrow(), "first\n");
+    let _ = vm.run("second>;§A;");
+    assert_eq!(*seen.borrow(), "first\nsecond");
+}
+
+#[test]
+fn io_sink_writes_utf8_and_undiverted_text() {
+    let mut vm = vm();
+    vm.set_output_sink(IoSink::new(Vec::new()));
+    let _ = vm.run("żółw §DIVERT,1;later§DIVERT; now §UNDIVERT,1;");
+    let sink = vm.take_output_sink::<IoSink<Vec<u8>>>().unwrap();
+    assert!(sink.error().is_none());
+    assert_eq!(String::from_utf8(sink.into_inner()).unwrap(), "żółw  now later");
+}
+
+struct Broken;
+
+impl io::Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("pipe closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn io_sink_keeps_the_first_error() {
+    let mut vm = vm();
+    vm.set_output_sink(IoSink::new(Broken));
+    assert_eq!(vm.run("abc"), "");
+    let sink = vm.take_output_sink::<IoSink<Broken>>().unwrap();
+    assert_eq!(sink.error().unwrap().to_string(), "pipe closed");
+}
+
+#[test]
+fn take_output_sink_of_another_type_leaves_it_in_place() {
+    let mut vm = vm();
+    vm.set_output_sink(String::new());
+    assert!(vm.take_output_sink::<IoSink<Vec<u8>>>().is_none());
+    assert_eq!(vm.run("a"), "");
+    assert_eq!(vm.take_output_sink::<String>().as_deref(), Some("a"));
+}
+
+#[test]
+fn diagnostic_sink_keeps_reports_out_of_the_output() {
+    let mut vm = vm();
+    vm.set_diagnostic_sink(String::new());
+    assert_eq!(vm.run("a §Nope; b"), "a  b");
+    assert_eq!(vm.run("§DEF,A,<~1>;§A,<"), "");
+    assert_eq!(vm.end(), "");
+
+    let diagnostics = vm.take_diagnostic_sink::<String>().unwrap();
+    let errors = vm.take_errors();
+    assert_eq!(errors.len(), 2);
+    assert!(diagnostics.contains("MONITOR: Undefined name Nope"));
+    // everything that was reported, nothing else
+    assert_eq!(diagnostics, format!("{}{}", errors[0].report, errors[1].report));
+}
+
+#[test]
+fn both_sinks_together() {
+    let mut vm = vm();
+    vm.set_output_sink(String::new());
+    vm.set_diagnostic_sink(String::new());
+    let _ = vm.run("x§Nope;y");
+    assert_eq!(vm.take_output_sink::<String>().as_deref(), Some("xy"));
+    assert!(vm.take_diagnostic_sink::<String>().unwrap().contains("Undefined name Nope"));
+}
@@ -0,0 +1,97 @@
+use gpm_in_rust::{Cell, ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn upper_and_lower_are_unicode_aware() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§UPPER,max_value;"), "MAX_VALUE");
+    assert_eq!(vm.run("§LOWER,<Zażółć GĘŚLĄ>;"), "zażółć gęślą");
+    // mapping may change the length
+    assert_eq!(vm.run("§UPPER,straße;"), "STRASSE");
+    assert_eq!(vm.run("[§UPPER,;]"), "[]");
+}
+
+#[test]
+fn case_builtins_work_inside_arguments() {
+    let mut vm = vm();
+
+    let out = vm.run(concat!(
+        "§DEF,Const,<pub const §UPPER,~1;: u32 = ~2;>;",
+        "§Const,max_len,8;\n§DEF,Name,§LOWER,ABC;;§Name;",
+    ));
+    assert_eq!(out, "pub const MAX_LEN: u32 = 8;\nabc");
+}
+
+#[test]
+fn class_dispatches_on_first_character() {
+    let mut vm = vm();
+
+    let class = |vm: &mut GpmVm, c: &str| vm.run(&format!("§CLASS,<{}>,L,D,S,O;", c));
+    assert_eq!(class(&mut vm, "a"), "L");
+    assert_eq!(class(&mut vm, "Ż"), "L");
+    assert_eq!(class(&mut vm, "λx"), "L");
+    assert_eq!(class(&mut vm, "7"), "D");
+    assert_eq!(class(&mut vm, "٣"), "D");
+    assert_eq!(class(&mut vm, " "), "S");
+    assert_eq!(class(&mut vm, "\n"), "S");
+    assert_eq!(class(&mut vm, "_"), "O");
+    assert_eq!(class(&mut vm, ""), "O");
+}
+
+#[test]
+fn class_selects_macro_names() {
+    let mut vm = vm();
+
+    let out = vm.run(concat!(
+        "§DEF,Id,<~1>;§DEF,Prefix,<_~1>;",
+        "§§CLASS,x1,Id,Prefix,Prefix,Prefix;,x1;/",
+        "§§CLASS,1x,Id,Prefix,Prefix,Prefix;,1x;",
+    ));
+    assert_eq!(out, "x1/_1x");
+}
+
+#[test]
+fn index_finds_first_occurrence() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§INDEX,gramatyka,a;"), "2");
+    assert_eq!(vm.run("§INDEX,<a,b,c>,<,c>;"), "3");
+    assert_eq!(vm.run("§INDEX,żółw,w;"), "3");
+    assert_eq!(vm.run("§INDEX,abc,x;"), "-1");
+    assert_eq!(vm.run("§INDEX,abc,abcd;"), "-1");
+    assert_eq!(vm.run("§INDEX,abc,;"), "0");
+}
+
+#[test]
+fn replace_escapes_generated_text() {
+    // < and > cannot be quoted on their own, so this VM quotes with [ ]
+    let cc = ControlChars { open: '[' as Cell, close: ']' as Cell, ..ControlChars::default() };
+    let mut html = GpmVm::new(cc, 10_000);
+    let out = html.run(concat!(
+        "§DEF,Html,[§REPLACE,§REPLACE,§REPLACE,~1,&,[&amp;];,<,[&lt;];,>,[&gt;];];",
+        "§Html,[<a href=x>Tom & Jerry</a>];",
+    ));
+    assert_eq!(out, "&lt;a href=x&gt;Tom &amp; Jerry&lt;/a&gt;");
+
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§REPLACE,aaaa,aa,b;"), "bb");
+    assert_eq!(vm.run("§REPLACE,<it's>,',<'\\''>;"), "it'\\''s");
+    assert_eq!(vm.run("§REPLACE,abc,,x;"), "abc");
+    assert_eq!(vm.run("§REPLACE,abc,b,;"), "ac");
+}
+
+#[test]
+fn translit_maps_and_deletes() {
+    let mut vm = vm();
+
+    assert_eq!(vm.run("§TRANSLIT,<my-crate name>,<- >,__;"), "my_crate_name");
+    assert_eq!(vm.run("§TRANSLIT,zażółć,ażółć,azolc;"), "zazolc");
+    // characters without a counterpart are dropped
+    assert_eq!(vm.run("§TRANSLIT,<a;b;c>,<;b>,;"), "ac");
+    assert_eq!(vm.run("§TRANSLIT,abc,,xyz;"), "abc");
+}
@@ -0,0 +1,83 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm(mem_size: usize) -> GpmVm {
+    GpmVm::new(ControlChars::default(), mem_size)
+}
+
+#[test]
+fn undef_uncovers_older_definition() {
+    let mut vm = vm(10_000);
+
+    let out = vm.run("§DEF,A,1;§DEF,A,2;§A;§UNDEF,A;§A;§UNDEF,A;§DEFINED,A,yes,no;");
+    assert_eq!(out, "21no");
+    // nothing left to undefine
+    assert_eq!(vm.run("§UNDEF,A;§UNDEF,Never;."), ".");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn undef_reclaims_store() {
+    // each DEF below needs ~25 cells, so without reclaiming the store fills up
+    let mut vm = vm(300);
+
+    for k in 0..50 {
+        let out = vm.run(&format!("§DEF,Temp,<value number {}>;§Temp;§UNDEF,Temp;", k));
+        assert_eq!(out, format!("value number {}", k));
+    }
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn undef_only_touches_top_level_definitions() {
+    let mut vm = vm(10_000);
+
+    // A made inside the first argument of Second lives only as long as that
+    // call, so UNDEF passes it by and removes the top-level one
+    let out = vm.run(concat!(
+        "§DEF,A,top;§DEF,Second,<~2>;",
+        "§Second,§DEF,A,local;§UNDEF,A;,§A;;|§DEFINED,A,yes,no;",
+    ));
+    assert_eq!(out, "local|no");
+
+    // a DEF in a body read at H = 0 outlives the call, so it is top-level
+    let out = vm.run("§DEF,A,top;§DEF,T,<§DEF,A,body;§UNDEF,A;§A;>;§T;|§A;");
+    assert_eq!(out, "top|top");
+}
+
+#[test]
+fn undef_relocates_active_calls() {
+    let mut vm = vm(10_000);
+
+    // the outer T is still collecting its arguments when the inner T
+    // removes A from underneath it
+    let out = vm.run(concat!(
+        "§DEF,A,aaaa;§DEF,B,bbb;",
+        "§DEF,T,<[~1|§UNDEF,A;§B;|~2]>;",
+        "§T,x,§T,y,z;;",
+        "§DEF,C,ccc;§C;§B;",
+    ));
+    assert_eq!(out, "[x|bbb|[y|bbb|z]]cccbbb");
+    assert_eq!(vm.end(), "");
+}
+
+#[test]
+fn undef_of_running_macro_only_unlinks() {
+    let mut vm = vm(10_000);
+
+    let out = vm.run("§DEF,S,<x§UNDEF,S;y>;§S;§DEFINED,S,yes,no;");
+    assert_eq!(out, "xyno");
+    let out = vm.run("§UNDEF,VAL;§DEFINED,VAL,yes,no;§DEFINED,DEF,yes,no;");
+    assert_eq!(out, "noyes");
+}
+
+#[test]
+fn defined_selects_branch_without_monitor() {
+    let mut vm = vm(10_000);
+
+    let out = vm.run(concat!(
+        "§DEF,Then,<is defined>;§DEF,Else,<is missing>;§DEF,X,1;",
+        "§§DEFINED,X,Then,Else;;/§§DEFINED,Y,Then,Else;;/",
+        "§DEFINED,Y,<a,b>;",
+    ));
+    assert_eq!(out, "is defined/is missing/");
+}
@@ -0,0 +1,41 @@
+use gpm_in_rust::{ControlChars, GpmVm};
+
+fn vm() -> GpmVm {
+    GpmVm::new(ControlChars::default(), 10_000)
+}
+
+#[test]
+fn var_reads_host_values() {
+    let mut vm = vm();
+    vm.set_var("version", "1.4.2");
+    vm.set_var("target", "x86_64-unknown-linux-gnu");
+
+    let out = vm.run("§DEF,Banner,<built §VAR,version; for ~1>;§Banner,§VAR,target;;");
+    assert_eq!(out, "built 1.4.2 for x86_64-unknown-linux-gnu");
+
+    vm.set_var("version", "2.0.0");
+    assert_eq!(vm.run("§VAR,version;"), "2.0.0");
+}
+
+#[test]
+fn control_characters_in_values_are_not_scanned() {
+    let mut vm = vm();
+    vm.set_var("flags", "<a;b>,~1§DEF,x,y;");
+
+    assert_eq!(vm.run("§VAR,flags;"), "<a;b>,~1§DEF,x,y;");
+    // nor when passed on as an argument
+    let out = vm.run("§DEF,Show,<[~1|~2]>;§Show,§VAR,flags;,second;");
+    assert_eq!(out, "[<a;b>,~1§DEF,x,y;|second]");
+    assert_eq!(vm.run("§INDEX,§VAR,flags;,<~>;"), "6");
+}
+
+#[test]
+fn unknown_var_is_an_undefined_name() {
+    let mut vm = vm();
+    vm.set_var("gone", "x");
+    assert_eq!(vm.remove_var("gone"), Some("x".to_string()));
+
+    let err = vm.try_run("§VAR,gone;").unwrap_err();
+    assert_eq!(err.monitor, 7);
+    assert_eq!(err.message, "Undefined name gone");
+}